@@ -19,11 +19,30 @@ use alloy_sol_types::{sol, SolCall};
 use anyhow::Context;
 use revm::{
     db::AccountState,
-    primitives::{Address, Bytes, ExecutionResult, keccak256, Output, TxKind, U256, state::{Account, AccountInfo}},
+    primitives::{Address, Bytes, ExecutionResult, keccak256, Output, SpecId, TxKind, U256, state::{Account, AccountInfo}},
     Database, DatabaseCommit, DatabaseRef, Evm,
 };
 use serde::de::DeserializeOwned;
 use tempfile::tempdir;
+use tracing::{debug, info, info_span, instrument, trace};
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// Installs the global subscriber.
+///
+/// The filter is read from `RUST_LOG` (for example
+/// `RUST_LOG=main_contract_test=debug` for every database operation) and
+/// defaults to `info`. Setting `LOG_FORMAT=json` emits one JSON object per
+/// event, with the fields of the enclosing spans.
+fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let is_json = std::env::var("LOG_FORMAT").is_ok_and(|format| format == "json");
+    let builder = fmt().with_env_filter(filter).with_target(true);
+    if is_json {
+        builder.json().with_current_span(true).with_span_list(true).init();
+    } else {
+        builder.init();
+    }
+}
 
 pub fn write_compilation_json(path: &Path, file_name: &str) {
     let mut source = File::create(path).unwrap();
@@ -39,6 +58,7 @@ pub fn write_compilation_json(path: &Path, file_name: &str) {
   }},
   "settings": {{
     "viaIR": true,
+    "evmVersion": "shanghai",
     "outputSelection": {{
       "*": {{
         "*": ["evm.bytecode"]
@@ -122,8 +142,14 @@ pub enum KeyTag {
     ZeroContractAddress,
     /// Key prefix for the storage of the contract address.
     ContractAddress,
+    /// First key prefix of the contracts registered by `register_contract_address`.
+    RegisteredContractAddress,
 }
 
+/// The number of addresses that `register_contract_address` accepts, the
+/// key tags being single bytes.
+const MAX_REGISTERED_ADDRESSES: usize = u8::MAX as usize + 1 - KeyTag::RegisteredContractAddress as usize;
+
 #[repr(u8)]
 pub enum KeyCategory {
     AccountInfo,
@@ -138,7 +164,7 @@ struct StorageStats {
     number_set: u64,
     number_release: u64,
     number_warm_read: u64,
-    map: BTreeMap<U256, U256>,
+    map: BTreeMap<(Address, U256), U256>,
 }
 
 struct LineraDatabase<C>
@@ -147,6 +173,7 @@ where
 {
     commit_error: Option<C::Error>,
     storage_stats: Arc<Mutex<StorageStats>>,
+    registered_addresses: Vec<Address>,
     db: C,
 }
 
@@ -193,16 +220,18 @@ impl<C> LineraDatabase<C>
 where
     C: TestKeyValueStore,
 {
+    #[instrument(name = "commit", skip_all, fields(accounts = changes.len()))]
     fn commit_with_error(
         &mut self,
         changes: HashMap<Address, Account>,
     ) -> Result<(), C::Error> {
-        println!("commit_with_error, beginning");
         let mut batch = Batch::new();
         let mut list_new_balances = Vec::new();
         let mut increment_number_reset = 0;
         let mut increment_number_set = 0;
         let mut increment_number_release = 0;
+        let mut cleared_addresses = Vec::new();
+        let mut written_slots = Vec::new();
         for (address, account) in changes {
             if !account.is_touched() {
                 continue;
@@ -212,16 +241,23 @@ where
                 let key_prefix = vec![val, KeyCategory::Storage as u8];
                 let key_info = vec![val, KeyCategory::AccountInfo as u8];
                 let key_state = vec![val, KeyCategory::AccountState as u8];
+                // Since EIP-6780 (Cancun), revm only marks an account as
+                // selfdestructed if it was created in the same transaction.
+                // Otherwise the SELFDESTRUCT only moves the balance to the
+                // beneficiary, which shows up as a plain balance change.
                 if account.is_selfdestructed() {
+                    debug!(%address, "selfdestruct");
                     batch.delete_key_prefix(key_prefix);
-                    batch.put_key_value(key_info, &AccountInfo::default())?;
+                    batch.delete_key(key_info);
                     batch.put_key_value(key_state, &AccountState::NotExisting)?;
+                    cleared_addresses.push(address);
                 } else {
                     let is_newly_created = account.is_created();
                     batch.put_key_value(key_info, &account.info)?;
 
                     let account_state = if is_newly_created {
                         batch.delete_key_prefix(key_prefix);
+                        cleared_addresses.push(address);
                         AccountState::StorageCleared
                     } else {
                         let result = block_on(self.db.read_value_bytes(&key_state))?;
@@ -235,26 +271,27 @@ where
                     };
                     batch.put_key_value(key_state, &account_state)?;
                     for (index, value) in account.storage {
+                        written_slots.push((address, index, value.present_value()));
                         let key = Self::get_uint256_key(val, index)?;
                         if value.original_value() == U256::ZERO {
                             if value.present_value() != U256::ZERO {
-                                println!("DB:   WRITE(A) index={} value={}", index, value.present_value());
+                                debug!(%address, slot = %index, value = %value.present_value(), case = "A", "storage write");
                                 batch.put_key_value(key, &value.present_value())?;
                                 increment_number_set += 1;
                             } else {
-                                println!("DB:   WRITE(B) index={} value={}", index, value.present_value());
+                                debug!(%address, slot = %index, value = %value.present_value(), case = "B", "storage write");
                             }
                         } else {
                             if value.present_value() != U256::ZERO {
                                 if value.present_value() == value.original_value() {
-                                    println!("DB:   WRITE(C) index={} value={}", index, value.present_value());
+                                    debug!(%address, slot = %index, value = %value.present_value(), case = "C", "storage write");
                                 } else {
-                                    println!("DB:   WRITE(D) index={} value={}", index, value.present_value());
+                                    debug!(%address, slot = %index, value = %value.present_value(), case = "D", "storage write");
                                     batch.put_key_value(key, &value.present_value())?;
                                     increment_number_reset += 1;
                                 }
                             } else {
-                                println!("DB:   WRITE(E) index={} value={}", index, value.present_value());
+                                debug!(%address, slot = %index, value = %value.present_value(), case = "E", "storage write");
                                 batch.delete_key(key);
                                 increment_number_release += 1;
                             }
@@ -275,8 +312,25 @@ where
         if !list_new_balances.is_empty() {
             panic!("The conversion Ethereum address / Linera address is not yet implemented");
         }
-        println!("increment reset={} set={} release={}", increment_number_reset, increment_number_set, increment_number_release);
+        debug!(
+            reset = increment_number_reset,
+            set = increment_number_set,
+            release = increment_number_release,
+            "commit done"
+        );
         let mut storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+        // The cached reads have to follow the committed state, otherwise a
+        // destroyed or re-created contract would see its former slots.
+        for ((address, _), value) in storage_stats.map.iter_mut() {
+            if cleared_addresses.contains(address) {
+                *value = U256::ZERO;
+            }
+        }
+        for (address, index, value) in written_slots {
+            if let Some(entry) = storage_stats.map.get_mut(&(address, index)) {
+                *entry = value;
+            }
+        }
         storage_stats.number_reset += increment_number_reset;
         storage_stats.number_set += increment_number_set;
         storage_stats.number_release += increment_number_release;
@@ -290,6 +344,7 @@ where
 {
     type Error = C::Error;
 
+    #[instrument(name = "basic", skip_all, fields(%address))]
     fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, C::Error> {
         self.throw_error()?;
         let val = self.get_contract_address_key(&address);
@@ -309,6 +364,7 @@ where
         panic!("Functionality code_by_hash_ref not implemented");
     }
 
+    #[instrument(name = "storage", skip_all, fields(%address, slot = %index))]
     fn storage_ref(&self, address: Address, index: U256) -> Result<U256, C::Error> {
         self.throw_error()?;
         let val = self.get_contract_address_key(&address);
@@ -316,18 +372,18 @@ where
             panic!("There is no storage associated to Externally Owned Account");
         };
         let mut storage_stats = self.storage_stats.lock().expect("The lock should be possible");
-        match storage_stats.map.entry(index) {
+        match storage_stats.map.entry((address, index)) {
             btree_map::Entry::Occupied(entry) => {
                 let result = *entry.get();
                 storage_stats.number_warm_read += 1;
-                println!("DB:   READ(A:WARM) index={} result={}", index, result);
+                debug!(value = %result, hot = true, "storage read");
                 Ok(result)
             },
             btree_map::Entry::Vacant(entry) => {
                 let key = Self::get_uint256_key(val, index)?;
                 let result = block_on(self.db.read_value_bytes(&key))?;
                 let result = from_bytes_option::<U256, ViewError>(&result).unwrap_or_default();
-                println!("DB:   READ(B:COLD) index={} result={}", index, result);
+                debug!(value = %result, hot = false, "storage read");
                 entry.insert(result);
                 Ok(result)
             },
@@ -353,7 +409,7 @@ where
     }
 
     fn get_contract_address_key(&self, address: &Address) -> Option<u8> {
-        println!("get_contract_address_key : address={}", address);
+        trace!(%address, "get_contract_address_key");
         let contract_address = Address::ZERO.create(0);
         if address == &Address::ZERO {
             return Some(KeyTag::ZeroContractAddress as u8);
@@ -361,7 +417,32 @@ where
         if address == &contract_address {
             return Some(KeyTag::ContractAddress as u8);
         }
-        None
+        let position = self.registered_addresses.iter().position(|x| x == address)?;
+        // The position is below `MAX_REGISTERED_ADDRESSES`, so the tag fits.
+        Some(KeyTag::RegisteredContractAddress as u8 + position as u8)
+    }
+
+    /// Contracts created by other contracts (for example with CREATE2) need
+    /// a key prefix before the transaction creating them is run. Only
+    /// `MAX_REGISTERED_ADDRESSES` of them can be registered.
+    fn register_contract_address(&mut self, address: Address) -> anyhow::Result<()> {
+        if self.get_contract_address_key(&address).is_some() {
+            return Ok(());
+        }
+        if self.registered_addresses.len() == MAX_REGISTERED_ADDRESSES {
+            anyhow::bail!("The key tags are exhausted, address={address} cannot be registered");
+        }
+        self.registered_addresses.push(address);
+        Ok(())
+    }
+
+    fn insert_account_info(&mut self, address: Address, info: AccountInfo) -> Result<(), C::Error> {
+        let Some(val) = self.get_contract_address_key(&address) else {
+            panic!("Only contract addresses can hold an account info address={address:?}");
+        };
+        let mut batch = Batch::new();
+        batch.put_key_value(vec![val, KeyCategory::AccountInfo as u8], &info)?;
+        block_on(self.db.write_batch(batch))
     }
 
     fn throw_error(&self) -> Result<(), C::Error> {
@@ -377,6 +458,7 @@ where
         Self {
             commit_error: None,
             storage_stats: Arc::new(Mutex::new(storage_stats)),
+            registered_addresses: Vec::new(),
             db,
         }
     }
@@ -388,11 +470,14 @@ where
 
     fn print_status(&self) {
         let storage_stats = self.storage_stats.lock().expect("The lock should be possible");
-        println!("    number_reset = {}", storage_stats.number_reset);
-        println!("      number_set = {}", storage_stats.number_set);
-        println!("  number_release = {}", storage_stats.number_release);
-        println!("number_warm_read = {}", storage_stats.number_warm_read);
-        println!("number_cold_read = {}", storage_stats.map.len());
+        info!(
+            number_reset = storage_stats.number_reset,
+            number_set = storage_stats.number_set,
+            number_release = storage_stats.number_release,
+            number_warm_read = storage_stats.number_warm_read,
+            number_cold_read = storage_stats.map.len(),
+            "storage stats"
+        );
     }
 
 }
@@ -409,11 +494,14 @@ enum Operation {
 
 fn deploy_contract<DB: Database + DatabaseRef + DatabaseCommit>(
     db: &mut DB,
+    spec_id: SpecId,
     bytecode: Bytes,
 ) -> anyhow::Result<Address> {
-    println!("deploy_contract |bytecode|={}", bytecode.as_ref().len());
+    let span = info_span!("transaction", kind = "create", ?spec_id, bytecode_len = bytecode.len());
+    let _guard = span.enter();
     let mut evm: Evm<'_, (), _> = Evm::builder()
         .with_ref_db(db)
+        .with_spec_id(spec_id)
         .modify_tx_env(|tx| {
             tx.clear();
             tx.transact_to = TxKind::Create;
@@ -421,9 +509,7 @@ fn deploy_contract<DB: Database + DatabaseRef + DatabaseCommit>(
         })
         .build();
 
-    println!("Before transact_commit, deploy");
     let result = evm.transact_commit();
-    println!(" After transact_commit, deploy");
     let Ok(result) = result else {
         anyhow::bail!("The transact_commit failed");
     };
@@ -439,25 +525,27 @@ fn deploy_contract<DB: Database + DatabaseRef + DatabaseCommit>(
 
 fn single_execution<DB: Database + DatabaseRef + DatabaseCommit>(
     db: &mut DB,
+    spec_id: SpecId,
+    contract_address: Address,
     encoded_args: Bytes,
 ) -> anyhow::Result<Bytes> {
-    let contract_address = Address::ZERO.create(0);
+    let span = info_span!("transaction", kind = "call", ?spec_id, %contract_address);
+    let _guard = span.enter();
     let mut evm: Evm<'_, (), _> = Evm::builder()
         .with_ref_db(db)
+        .with_spec_id(spec_id)
         .modify_tx_env(|tx| {
             tx.transact_to = TxKind::Call(contract_address);
             tx.data = encoded_args;
         })
         .build();
 
-    println!("Before transact_commit, call");
     let result = evm.transact_commit();
-    println!(" After transact_commit, call");
     let Ok(result) = result else {
         anyhow::bail!("The transact_commit failed");
     };
 
-    println!("result={:?}", result);
+    debug!(?result, "transaction executed");
 
     let ExecutionResult::Success { output, .. } = result else {
         anyhow::bail!("Execution did not work out")
@@ -470,9 +558,11 @@ fn single_execution<DB: Database + DatabaseRef + DatabaseCommit>(
 
 fn single_execution_operation<DB: Database + DatabaseRef + DatabaseCommit>(
     db: &mut DB,
+    spec_id: SpecId,
     operation: Operation,
 ) -> anyhow::Result<()> {
-    println!("--------------------------- operation={operation:?} ---------------------------------------");
+    let span = info_span!("operation", ?operation);
+    let _guard = span.enter();
     sol! {
         function insert_key_value(uint256 key, uint256 value);
         function insert_key_value_bis(uint256 key, uint256 value);
@@ -498,14 +588,17 @@ fn single_execution_operation<DB: Database + DatabaseRef + DatabaseCommit>(
             fct_args.abi_encode().into()
         },
     };
-    single_execution(db, encoded_args)?;
+    let contract_address = Address::ZERO.create(0);
+    single_execution(db, spec_id, contract_address, encoded_args)?;
     Ok(())
 }
 
 
 
 
-fn main() -> anyhow::Result<()> {
+fn key_value_map_scenario(spec_id: SpecId) -> anyhow::Result<()> {
+    let span = info_span!("key_value_map_scenario", ?spec_id);
+    let _guard = span.enter();
     let bytecode = {
         let source_code = r#"
 contract ExampleKeyValueMap {
@@ -541,7 +634,7 @@ contract ExampleKeyValueMap {
 
     let db = block_on(MemoryStore::new_test_store()).unwrap();
     let mut db = LineraDatabase::new(db);
-    let contract_address = deploy_contract(&mut db, tx_data)?;
+    let contract_address = deploy_contract(&mut db, spec_id, tx_data)?;
     assert_eq!(contract_address, Address::ZERO.create(0));
 
 
@@ -554,11 +647,152 @@ contract ExampleKeyValueMap {
                       Operation::DeleteKey(U256::from(7)),
                       Operation::ReadValue(U256::from(7)),
                       Operation::ReadValue(U256::from(5))] {
-        single_execution_operation(&mut db, operation)?;
+        single_execution_operation(&mut db, spec_id, operation)?;
         db.print_status();
         db.reset_storage_stats();
     }
+    Ok(())
+}
+
+/// Runs the SELFDESTRUCT cases whose outcome depends on EIP-6780:
+/// destruction in the creating transaction, destruction in a later
+/// transaction and re-creation at the same address with CREATE2.
+fn selfdestruct_scenario(spec_id: SpecId) -> anyhow::Result<()> {
+    let span = info_span!("selfdestruct_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Child {
+  uint256 value;
+
+  constructor() payable {
+    value = 1;
+  }
+
+  function set_value(uint256 new_value) external {
+    value = new_value;
+  }
+
+  function get_value() external view returns (uint256) {
+    return value;
+  }
+
+  function destroy(address payable beneficiary) external {
+    selfdestruct(beneficiary);
+  }
+}
+
+contract Factory {
+  bytes32 constant SALT = bytes32(uint256(42));
+
+  function create_child(uint256 amount) external returns (address) {
+    Child child = new Child{salt: SALT, value: amount}();
+    return address(child);
+  }
+
+  function create_and_destroy_child(uint256 amount) external returns (address) {
+    Child child = new Child{salt: SALT, value: amount}();
+    child.destroy(payable(address(this)));
+    return address(child);
+  }
+}
+"#
+    .to_string();
+    let child_bytecode = get_bytecode(&source_code, "Child")?;
+    let factory_bytecode = get_bytecode(&source_code, "Factory")?;
+
+    sol! {
+        function create_child(uint256 amount) returns (address);
+        function create_and_destroy_child(uint256 amount) returns (address);
+        function set_value(uint256 new_value);
+        function get_value() returns (uint256);
+        function destroy(address beneficiary);
+    }
+
+    let is_cancun = spec_id.is_enabled_in(SpecId::CANCUN);
+    let initial_balance = U256::from(1000);
+    let amount = U256::from(10);
+    let factory_address = Address::ZERO.create(0);
+    let salt = B256::from(U256::from(42));
+    let child_address = factory_address.create2_from_code(salt, &child_bytecode);
+
+    let db = block_on(MemoryStore::new_test_store()).unwrap();
+    let mut db = LineraDatabase::new(db);
+    db.register_contract_address(child_address)?;
+    // Funding the address before the deployment keeps the creating
+    // transaction free of value transfers from the caller.
+    db.insert_account_info(factory_address, AccountInfo::from_balance(initial_balance))?;
+    let contract_address = deploy_contract(&mut db, spec_id, factory_bytecode)?;
+    assert_eq!(contract_address, factory_address);
+
+    // Case 1: created and destroyed in the same transaction. The contract
+    // disappears and its balance goes back to the factory for all specs.
+    let fct_args = create_and_destroy_childCall { amount };
+    let output = single_execution(&mut db, spec_id, factory_address, fct_args.abi_encode().into())?;
+    let address = create_and_destroy_childCall::abi_decode_returns(&output, true)?._0;
+    assert_eq!(address, child_address);
+    assert_eq!(db.basic_ref(child_address)?, None);
+    assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::ZERO);
+    let factory_info = db.basic_ref(factory_address)?.context("factory should exist")?;
+    assert_eq!(factory_info.balance, initial_balance);
+
+    // Case 2: created in one transaction and destroyed in a later one.
+    let fct_args = create_childCall { amount };
+    let output = single_execution(&mut db, spec_id, factory_address, fct_args.abi_encode().into())?;
+    let address = create_childCall::abi_decode_returns(&output, true)?._0;
+    assert_eq!(address, child_address);
+    let fct_args = set_valueCall { new_value: U256::from(5) };
+    single_execution(&mut db, spec_id, child_address, fct_args.abi_encode().into())?;
+    let fct_args = destroyCall { beneficiary: factory_address };
+    single_execution(&mut db, spec_id, child_address, fct_args.abi_encode().into())?;
+
+    let factory_info = db.basic_ref(factory_address)?.context("factory should exist")?;
+    assert_eq!(factory_info.balance, initial_balance);
+    let child_info = db.basic_ref(child_address)?;
+    if is_cancun {
+        // Only the balance is moved, code and storage are kept.
+        let child_info = child_info.context("child should survive SELFDESTRUCT")?;
+        assert_eq!(child_info.balance, U256::ZERO);
+        assert!(!child_info.is_empty_code_hash());
+        assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::from(5));
+        let output = single_execution(&mut db, spec_id, child_address, get_valueCall {}.abi_encode().into())?;
+        assert_eq!(get_valueCall::abi_decode_returns(&output, true)?._0, U256::from(5));
+    } else {
+        assert_eq!(child_info, None);
+        assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::ZERO);
+    }
+    db.print_status();
+    db.reset_storage_stats();
+
+    // Case 3: re-creation at the same address. This is only possible if the
+    // previous contract was really destroyed.
+    let fct_args = create_childCall { amount: U256::ZERO };
+    let result = single_execution(&mut db, spec_id, factory_address, fct_args.abi_encode().into());
+    if is_cancun {
+        assert!(result.is_err(), "CREATE2 should collide with the surviving contract");
+        assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::from(5));
+    } else {
+        let address = create_childCall::abi_decode_returns(&result?, true)?._0;
+        assert_eq!(address, child_address);
+        // The storage of the re-created contract starts from scratch.
+        assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::from(1));
+        let output = single_execution(&mut db, spec_id, child_address, get_valueCall {}.abi_encode().into())?;
+        assert_eq!(get_valueCall::abi_decode_returns(&output, true)?._0, U256::from(1));
+    }
+    db.print_status();
+    Ok(())
+}
+
+
+fn main() -> anyhow::Result<()> {
+    init_tracing();
+    for spec_id in [SpecId::SHANGHAI, SpecId::CANCUN] {
+        key_value_map_scenario(spec_id)?;
+        selfdestruct_scenario(spec_id)?;
+    }
 
-    println!("The single_execution has been successful");
+    info!("The single_execution has been successful");
     Ok(())
 }
@@ -97,10 +97,20 @@ pub fn get_bytecode(source_code: &str, contract_name: &str) -> anyhow::Result<By
     get_bytecode_path(path, file_name, contract_name)
 }
 
+/// The nonce checks of revm 22 are active, so the nonce of the caller has
+/// to be read from the database for every transaction.
+fn caller_nonce<DB: DatabaseRef>(database: &DB, caller: Address) -> anyhow::Result<u64> {
+    let Ok(info) = database.basic_ref(caller) else {
+        anyhow::bail!("The basic_ref failed for caller={caller}");
+    };
+    Ok(info.map(|info| info.nonce).unwrap_or_default())
+}
+
 fn deploy_contract<DB: Database + DatabaseRef + DatabaseCommit>(
     database: &mut DB,
     bytecode: Bytes,
 ) -> anyhow::Result<Address> {
+    let nonce = caller_nonce(database, Address::ZERO)?;
     let ctx: revm_context::Context<BlockEnv, _, _, _, Journal<WrapDatabaseRef<&mut DB>>, ()> =
         revm_context::Context::new(WrapDatabaseRef(database), SpecId::default());
     let instructions = EthInstructions::new_mainnet();
@@ -108,7 +118,7 @@ fn deploy_contract<DB: Database + DatabaseRef + DatabaseCommit>(
     let result = evm.transact_commit(TxEnv {
         kind: TxKind::Create,
         data: bytecode,
-        nonce: 0,
+        nonce,
         ..TxEnv::default()
     });
     println!("1: result={:?}", result);
@@ -129,6 +139,7 @@ fn single_execution<DB: Database + DatabaseRef + DatabaseCommit>(
     contract_address: Address,
     encoded_args: Bytes,
 ) -> anyhow::Result<InterpreterResult> {
+    let nonce = caller_nonce(database, Address::ZERO)?;
     let ctx: revm_context::Context<BlockEnv, _, _, _, Journal<WrapDatabaseRef<&mut DB>>, ()> =
         revm_context::Context::new(WrapDatabaseRef(database), SpecId::default());
     let instructions = EthInstructions::new_mainnet();
@@ -136,7 +147,7 @@ fn single_execution<DB: Database + DatabaseRef + DatabaseCommit>(
     let result = evm.transact_commit(TxEnv {
         kind: TxKind::Call(contract_address),
         data: encoded_args,
-        nonce: 1,
+        nonce,
         ..TxEnv::default()
     });
     println!("2: result={:?}", result);
@@ -256,6 +267,7 @@ contract ExternalCodes {
     let fct_args = test_external_codesCall { input };
     let fct_args = fct_args.abi_encode().into();
 
+    let nonce = caller_nonce(&database, Address::ZERO)?;
     let ctx: revm_context::Context<
         BlockEnv,
         _,
@@ -276,7 +288,7 @@ contract ExternalCodes {
         TxEnv {
             kind: TxKind::Call(contract_address),
             data: fct_args,
-            nonce: 1,
+            nonce,
             ..TxEnv::default()
         },
         inspector,
@@ -0,0 +1,148 @@
+//! Compares the cost of a transaction against `InMemoryDB` and against
+//! `LineraDatabase<MemoryStore>`, and reports the number of store calls per
+//! transaction of the latter.
+//!
+//! The `LineraDatabase` is measured twice: `warm` keeps the slots read by the
+//! previous transactions in the cache of `StorageStats`, `cold` resets it
+//! before every transaction, as for a database created per block.
+//!
+//! Run with `cargo bench --bench database`.
+
+use std::{
+    hint::black_box,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion, Throughput, measurement::WallTime};
+use linera_views::memory::MemoryStore;
+use revm::primitives::Bytes;
+use revm_database::InMemoryDB;
+use revm_primitives::{hardfork::SpecId, Address, U256};
+use test_linera_database::{
+    backend::Backend,
+    harness::ContractHarness,
+    linera_database::LineraDatabase,
+    metrics::StorageMetrics,
+    workloads::{erc20_bytecode, key_value_map_bytecode, transfer_args, Operation},
+};
+
+const SPEC_ID: SpecId = SpecId::CANCUN;
+
+/// The number of transactions used to count the store calls.
+const NUMBER_COUNTED_TRANSACTIONS: usize = 100;
+
+fn key_value_map_transactions() -> Vec<Bytes> {
+    [
+        Operation::DeleteKey(U256::from(7)),
+        Operation::InsertKeyValue(U256::from(7), U256::from(5)),
+        Operation::InsertKeyValue(U256::from(7), U256::from(5)),
+        Operation::InsertKeyValue(U256::from(7), U256::from(7)),
+        Operation::InsertKeyValueBis(U256::from(7), U256::from(5)),
+        Operation::ReadValue(U256::from(7)),
+        Operation::DeleteKey(U256::from(7)),
+        Operation::ReadValue(U256::from(7)),
+        Operation::ReadValue(U256::from(5)),
+    ]
+    .iter()
+    .map(Operation::abi_encode)
+    .collect()
+}
+
+/// Transfers to 200 recipients: the first round sets new slots, the next
+/// ones reset them.
+fn erc20_transactions() -> Vec<Bytes> {
+    (1..=200u8)
+        .map(|recipient| transfer_args(Address::with_last_byte(recipient), U256::from(1)))
+        .collect()
+}
+
+/// Benchmarks the calls of `transactions` in turn, `before_call` being run
+/// outside of the measurement before each of them.
+fn run_workload<DB: Backend>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    name: &str,
+    harness: &mut ContractHarness<DB>,
+    contract_address: Address,
+    transactions: &[Bytes],
+    before_call: impl Fn(&DB),
+) {
+    let mut index = 0;
+    group.bench_function(name, |b| {
+        b.iter_custom(|iters| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..iters {
+                before_call(harness.db());
+                let encoded_args = transactions[index % transactions.len()].clone();
+                index += 1;
+                let start = Instant::now();
+                let receipt = harness
+                    .call(contract_address, encoded_args)
+                    .expect("The transaction should succeed");
+                elapsed += start.elapsed();
+                black_box(receipt);
+            }
+            elapsed
+        })
+    });
+}
+
+fn report_store_calls(
+    name: &str,
+    metrics: &StorageMetrics,
+    harness: &mut ContractHarness<LineraDatabase<MemoryStore>>,
+    contract_address: Address,
+    transactions: &[Bytes],
+    before_call: impl Fn(&LineraDatabase<MemoryStore>),
+) {
+    let before = metrics.store_calls();
+    for encoded_args in transactions.iter().cycle().take(NUMBER_COUNTED_TRANSACTIONS) {
+        before_call(harness.db());
+        harness
+            .call(contract_address, encoded_args.clone())
+            .expect("The transaction should succeed");
+    }
+    let store_calls = metrics.store_calls() - before;
+    println!(
+        "{name}: {:.2} store calls per transaction",
+        store_calls as f64 / NUMBER_COUNTED_TRANSACTIONS as f64
+    );
+}
+
+fn bench_workload(c: &mut Criterion, workload: &str, bytecode: Bytes, transactions: Vec<Bytes>) {
+    let mut group = c.benchmark_group(workload);
+    group.throughput(Throughput::Elements(1));
+
+    let mut harness = ContractHarness::new(InMemoryDB::default(), SPEC_ID);
+    let contract_address = harness
+        .deploy(bytecode.clone())
+        .expect("The deployment should succeed");
+    run_workload(&mut group, "InMemoryDB", &mut harness, contract_address, &transactions, |_| {});
+
+    let metrics = Arc::new(StorageMetrics::new());
+    let mut harness = ContractHarness::new_linera(SPEC_ID, &metrics).expect("The store should be created");
+    let contract_address = harness.deploy(bytecode).expect("The deployment should succeed");
+    let keep_cache = |_: &LineraDatabase<MemoryStore>| {};
+    let name = format!("{workload}/LineraDatabase/warm");
+    report_store_calls(&name, &metrics, &mut harness, contract_address, &transactions, keep_cache);
+    run_workload(&mut group, "LineraDatabase/warm", &mut harness, contract_address, &transactions, keep_cache);
+    let reset_cache = |db: &LineraDatabase<MemoryStore>| db.reset_storage_stats();
+    let name = format!("{workload}/LineraDatabase/cold");
+    report_store_calls(&name, &metrics, &mut harness, contract_address, &transactions, reset_cache);
+    run_workload(&mut group, "LineraDatabase/cold", &mut harness, contract_address, &transactions, reset_cache);
+
+    group.finish();
+}
+
+fn key_value_map(c: &mut Criterion) {
+    let bytecode = key_value_map_bytecode().expect("The compilation should succeed");
+    bench_workload(c, "key_value_map", bytecode, key_value_map_transactions());
+}
+
+fn erc20_transfer(c: &mut Criterion) {
+    let bytecode = erc20_bytecode().expect("The compilation should succeed");
+    bench_workload(c, "erc20_transfer", bytecode, erc20_transactions());
+}
+
+criterion_group!(benches, key_value_map, erc20_transfer);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+contract ExampleKeyValueMap {
+  mapping(uint256 => uint256) map;
+
+  function insert_key_value(uint256 key, uint256 value) external returns (uint256) {
+    map[key] = value;
+  }
+
+  function insert_key_value_bis(uint256 key, uint256 value) external returns (uint256) {
+    map[key] = value;
+    map[key] = value + 1;
+  }
+
+  function delete_key(uint256 key) external returns (uint256) {
+    delete map[key];
+  }
+
+  function read_value(uint256 key) external returns (uint256) {
+    return map[key];
+  }
+}
@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use revm::{
+    bytecode::opcode,
+    inspector::{InspectEvm, Inspector},
+    interpreter::{interpreter::EthInterpreter, interpreter_types::Jumps, Interpreter},
+    precompile::{PrecompileSpecId, Precompiles},
+};
+use revm_context::{
+    result::ResultAndState,
+    transaction::{AccessList, AccessListItem},
+    BlockEnv, Evm, Journal, TxEnv,
+};
+use revm_database::WrapDatabaseRef;
+use revm_handler::{instructions::EthInstructions, EthPrecompiles};
+use revm_primitives::{hardfork::SpecId, Address, TxKind, B256};
+use tracing::{info, info_span};
+
+use crate::{backend::Backend, harness::ContractHarness};
+
+const EIP2930_TX_TYPE: u8 = 1;
+
+/// Collects the addresses and storage keys touched by a transaction, as
+/// `eth_createAccessList` does.
+#[derive(Clone, Debug, Default)]
+pub struct AccessListInspector {
+    touched: BTreeMap<Address, BTreeSet<B256>>,
+}
+
+impl AccessListInspector {
+    fn touch_address(&mut self, address: Address) {
+        self.touched.entry(address).or_default();
+    }
+
+    /// The touched entries, without the addresses of `excluded` that are
+    /// warm anyway unless some of their storage keys are touched.
+    pub fn access_list(&self, excluded: &BTreeSet<Address>) -> Vec<(Address, Vec<B256>)> {
+        self.touched
+            .iter()
+            .filter(|(address, keys)| !keys.is_empty() || !excluded.contains(*address))
+            .map(|(address, keys)| (*address, keys.iter().copied().collect()))
+            .collect()
+    }
+}
+
+impl<CTX> Inspector<CTX, EthInterpreter> for AccessListInspector {
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        match interp.bytecode.opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                if let Ok(slot) = interp.stack.peek(0) {
+                    let address = interp.input.target_address;
+                    self.touched.entry(address).or_default().insert(B256::from(slot));
+                }
+            }
+            opcode::EXTCODECOPY | opcode::EXTCODEHASH | opcode::EXTCODESIZE | opcode::BALANCE | opcode::SELFDESTRUCT => {
+                if let Ok(word) = interp.stack.peek(0) {
+                    self.touch_address(Address::from_word(B256::from(word)));
+                }
+            }
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL => {
+                if let Ok(word) = interp.stack.peek(1) {
+                    self.touch_address(Address::from_word(B256::from(word)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The access list of a transaction and its gas used with and without it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessListReport {
+    pub access_list: Vec<(Address, Vec<B256>)>,
+    pub gas_used_without: u64,
+    pub gas_used_with: u64,
+}
+
+impl AccessListReport {
+    /// Whether sending the transaction with the access list is cheaper.
+    /// Every entry costs 2400 gas per address and 1900 per storage key and
+    /// saves 2500 and 2000 on the first access, so listing an address
+    /// without keys that is warm anyway, or a slot accessed only in a
+    /// failed branch, makes it more expensive.
+    pub fn saves_gas(&self) -> bool {
+        self.gas_used_with < self.gas_used_without
+    }
+}
+
+impl<DB> ContractHarness<DB>
+where
+    DB: Backend,
+{
+    /// Executes `tx`, with its nonce as given, with an
+    /// `AccessListInspector` and without committing it.
+    pub fn inspect_access_list(&mut self, tx: TxEnv) -> anyhow::Result<(ResultAndState, AccessListInspector)> {
+        let spec_id = self.spec_id();
+        let block = self.block().clone();
+        let mut ctx: revm_context::Context<BlockEnv, _, _, _, Journal<WrapDatabaseRef<&mut DB>>, ()> =
+            revm_context::Context::new(WrapDatabaseRef(self.db_mut()), spec_id);
+        ctx.block = block;
+        let instructions = EthInstructions::new_mainnet();
+        let mut evm =
+            Evm::new_with_inspector(ctx, AccessListInspector::default(), instructions, EthPrecompiles::default());
+        let result = evm.inspect_with_tx(tx);
+        let result_and_state = match result {
+            Ok(result_and_state) => result_and_state,
+            Err(error) => anyhow::bail!("The inspect failed: {error:?}"),
+        };
+        Ok((result_and_state, std::mem::take(&mut evm.inspector)))
+    }
+
+    /// Generates the EIP-2930 access list of `tx`, with its nonce as given,
+    /// and compares the gas used with and without it. Nothing is committed.
+    ///
+    /// The sender, the recipient, the created contract, the precompiles and
+    /// (since Shanghai) the coinbase are warm from the start, so they are
+    /// only listed for their storage keys.
+    pub fn create_access_list(&mut self, tx: TxEnv) -> anyhow::Result<AccessListReport> {
+        let span = info_span!("create_access_list", caller = %tx.caller, kind = ?tx.kind);
+        let _guard = span.enter();
+        let (result_and_state, inspector) = self.inspect_access_list(tx.clone())?;
+        let gas_used_without = result_and_state.result.gas_used();
+
+        let spec_id = self.spec_id();
+        let mut excluded = Precompiles::new(PrecompileSpecId::from_spec_id(spec_id))
+            .addresses()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        excluded.insert(tx.caller);
+        excluded.insert(match tx.kind {
+            TxKind::Call(address) => address,
+            TxKind::Create => tx.caller.create(tx.nonce),
+        });
+        if spec_id.is_enabled_in(SpecId::SHANGHAI) {
+            excluded.insert(self.block().beneficiary);
+        }
+        let access_list = inspector.access_list(&excluded);
+
+        let items = access_list
+            .iter()
+            .map(|(address, storage_keys)| AccessListItem {
+                address: *address,
+                storage_keys: storage_keys.clone(),
+            })
+            .collect::<Vec<_>>();
+        let mut tx = tx;
+        tx.access_list = AccessList::from(items);
+        if tx.tx_type < EIP2930_TX_TYPE {
+            tx.tx_type = EIP2930_TX_TYPE;
+        }
+        let gas_used_with = self.simulate(tx)?.gas_used();
+        info!(entries = access_list.len(), gas_used_without, gas_used_with, "access list created");
+        let report = AccessListReport {
+            access_list,
+            gas_used_without,
+            gas_used_with,
+        };
+        Ok(report)
+    }
+}
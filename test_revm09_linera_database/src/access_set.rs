@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use revm_primitives::{Address, U256};
+
+/// An entry of the state as seen by the transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AccessKey {
+    AccountInfo(Address),
+    Storage(Address, U256),
+    /// Every slot of the contract, for the contracts created or destroyed
+    /// by the transaction whose storage is cleared.
+    AllStorage(Address),
+}
+
+impl AccessKey {
+    /// Whether a write of `self` changes what a read of `read` returns.
+    pub fn overlaps(&self, read: &AccessKey) -> bool {
+        match (self, read) {
+            (AccessKey::AllStorage(address), AccessKey::Storage(read_address, _))
+            | (AccessKey::AllStorage(address), AccessKey::AllStorage(read_address)) => {
+                address == read_address
+            }
+            _ => self == read,
+        }
+    }
+}
+
+/// The entries read and written by one transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessSets {
+    pub reads: BTreeSet<AccessKey>,
+    pub writes: BTreeSet<AccessKey>,
+}
+
+/// The transaction `transaction` read the entry `key` that the earlier
+/// transaction `writer` wrote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub transaction: usize,
+    pub writer: usize,
+    pub key: AccessKey,
+}
+
+/// Validates transactions executed speculatively against the same base
+/// state, to be committed in the order of the slice.
+///
+/// The result of a transaction is valid if none of its reads was written by
+/// an earlier transaction: it is then the one of the serial execution. For
+/// every other transaction, the conflict with the earliest writer is
+/// returned and the transaction has to be re-executed after the commit of
+/// the earlier ones. Blind writes need no check since the commit happens in
+/// order.
+///
+/// The writes of a conflicting transaction are the ones of its speculative
+/// execution. Its re-execution may write other entries, so the transactions
+/// after it have to be validated again with its new access sets.
+pub fn find_conflicts(access_sets: &[AccessSets]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut writers = BTreeMap::<AccessKey, usize>::new();
+    for (transaction, sets) in access_sets.iter().enumerate() {
+        let conflict = writers
+            .iter()
+            .filter_map(|(key, writer)| {
+                let read = sets.reads.iter().find(|read| key.overlaps(read))?;
+                Some(Conflict {
+                    transaction,
+                    writer: *writer,
+                    key: *read,
+                })
+            })
+            .min_by_key(|conflict| conflict.writer);
+        if let Some(conflict) = conflict {
+            conflicts.push(conflict);
+        }
+        for key in &sets.writes {
+            writers.entry(*key).or_insert(transaction);
+        }
+    }
+    conflicts
+}
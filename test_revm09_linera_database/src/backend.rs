@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use linera_views::store::TestKeyValueStore;
+use revm_database::{CacheDB, Database, DatabaseCommit, DatabaseRef};
+use revm_primitives::{Address, U256};
+use revm_state::{Account, AccountInfo, EvmState, EvmStorageSlot};
+
+use crate::linera_database::{CommitError, LineraDatabase, StorageCounts};
+
+/// The databases that the `ContractHarness`, the scenario files, the
+/// differential checks and the state tests run against.
+pub trait Backend: Database + DatabaseRef + DatabaseCommit {
+    /// Commits `changes`, or leaves the database unchanged and returns why
+    /// they are rejected, as the `LineraDatabase` does for the storage
+    /// quotas.
+    fn try_commit(&mut self, changes: EvmState) -> anyhow::Result<()> {
+        self.commit(changes);
+        Ok(())
+    }
+
+    /// Makes `address` usable by the transactions, with `info` if given.
+    /// The `LineraDatabase` needs it for the users and for the contracts
+    /// not deployed by `Address::ZERO` with nonce 0.
+    fn add_account(&mut self, address: Address, info: Option<AccountInfo>) -> anyhow::Result<()>;
+
+    /// The addresses holding an account info or storage.
+    fn addresses(&self) -> anyhow::Result<Vec<Address>>;
+
+    /// The non-zero slots of `address`.
+    fn storage_slots(&self, address: Address) -> anyhow::Result<BTreeMap<U256, U256>>;
+
+    /// The storage operations since the creation of the database or the last
+    /// `LineraDatabase::reset_storage_stats`, if they are counted.
+    fn storage_counts(&self) -> Option<StorageCounts>;
+
+    /// Creates the account at `address` with `info` and the non-zero slots
+    /// of `storage`, as in a pre-state or a genesis alloc. It is written
+    /// through a commit, so the `LineraDatabase` records the usage of the
+    /// slots as for a deployment.
+    fn insert_account(&mut self, address: Address, info: AccountInfo, storage: &BTreeMap<U256, U256>) -> anyhow::Result<()> {
+        self.add_account(address, None)?;
+        let mut account = Account::from(info);
+        for (slot, value) in storage {
+            if *value != U256::ZERO {
+                account.storage.insert(*slot, EvmStorageSlot::new_changed(U256::ZERO, *value));
+            }
+        }
+        account.mark_touch();
+        account.mark_created();
+        self.try_commit([(address, account)].into_iter().collect())
+    }
+}
+
+/// The `InMemoryDB` of revm, and the overlays used by `execute_block`.
+impl<ExtDB: DatabaseRef> Backend for CacheDB<ExtDB> {
+    fn add_account(&mut self, address: Address, info: Option<AccountInfo>) -> anyhow::Result<()> {
+        if let Some(info) = info {
+            self.insert_account_info(address, info);
+        }
+        Ok(())
+    }
+
+    fn addresses(&self) -> anyhow::Result<Vec<Address>> {
+        Ok(self.cache.accounts.keys().copied().collect())
+    }
+
+    /// Only the cached slots, which are all of them for the `InMemoryDB`.
+    fn storage_slots(&self, address: Address) -> anyhow::Result<BTreeMap<U256, U256>> {
+        let Some(account) = self.cache.accounts.get(&address) else {
+            return Ok(BTreeMap::new());
+        };
+        Ok(account
+            .storage
+            .iter()
+            .filter(|(_, value)| **value != U256::ZERO)
+            .map(|(slot, value)| (*slot, *value))
+            .collect())
+    }
+
+    fn storage_counts(&self) -> Option<StorageCounts> {
+        None
+    }
+}
+
+impl<C> Backend for LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: Send + Sync + 'static,
+{
+    fn try_commit(&mut self, changes: EvmState) -> anyhow::Result<()> {
+        match self.commit_checked(changes) {
+            Ok(()) => Ok(()),
+            Err(CommitError::Quota(error)) => Err(error.into()),
+            Err(CommitError::Store(error)) => Err(error.into()),
+        }
+    }
+
+    fn add_account(&mut self, address: Address, info: Option<AccountInfo>) -> anyhow::Result<()> {
+        self.register_contract_address(address);
+        if let Some(info) = info {
+            self.insert_account_info(address, info)?;
+        }
+        Ok(())
+    }
+
+    fn addresses(&self) -> anyhow::Result<Vec<Address>> {
+        let accounts = self.dump_all()?.accounts;
+        Ok(accounts
+            .into_iter()
+            .filter(|account| account.is_known)
+            .map(|account| account.address)
+            .collect())
+    }
+
+    fn storage_slots(&self, address: Address) -> anyhow::Result<BTreeMap<U256, U256>> {
+        Ok(self.dump_account(address)?.storage)
+    }
+
+    fn storage_counts(&self) -> Option<StorageCounts> {
+        Some(LineraDatabase::storage_counts(self))
+    }
+}
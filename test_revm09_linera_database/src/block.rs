@@ -0,0 +1,182 @@
+use alloy_consensus::{proofs::calculate_receipt_root, Eip658Value, Receipt, ReceiptEnvelope, ReceiptWithBloom};
+use anyhow::Context;
+use revm_context::{
+    result::{ExecutionResult, Output},
+    BlockEnv, TxEnv,
+};
+use revm_database::CacheDB;
+use revm_primitives::{
+    alloy_primitives::{logs_bloom, Bloom},
+    Address, Log, B256,
+};
+use revm_state::EvmState;
+use tracing::{info, info_span};
+
+use crate::{backend::Backend, harness::ContractHarness};
+
+/// The receipt of a transaction of a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockReceipt {
+    pub tx_type: u8,
+    pub success: bool,
+    pub gas_used: u64,
+    /// The gas used by the block up to this transaction included.
+    pub cumulative_gas_used: u64,
+    pub logs: Vec<Log>,
+    pub logs_bloom: Bloom,
+    /// The address of the contract created by the transaction.
+    pub contract_address: Option<Address>,
+}
+
+impl BlockReceipt {
+    /// The consensus encoding of the receipt, as hashed in the receipts
+    /// root.
+    pub fn envelope(&self) -> ReceiptEnvelope {
+        let receipt = ReceiptWithBloom {
+            receipt: Receipt {
+                status: Eip658Value::Eip658(self.success),
+                cumulative_gas_used: self.cumulative_gas_used,
+                logs: self.logs.clone(),
+            },
+            logs_bloom: self.logs_bloom,
+        };
+        match self.tx_type {
+            1 => ReceiptEnvelope::Eip2930(receipt),
+            2 => ReceiptEnvelope::Eip1559(receipt),
+            3 => ReceiptEnvelope::Eip4844(receipt),
+            4 => ReceiptEnvelope::Eip7702(receipt),
+            _ => ReceiptEnvelope::Legacy(receipt),
+        }
+    }
+}
+
+/// The result of `execute_block`.
+#[derive(Clone, Debug)]
+pub struct BlockOutcome {
+    pub receipts: Vec<BlockReceipt>,
+    pub gas_used: u64,
+    pub logs_bloom: Bloom,
+    pub receipts_root: B256,
+}
+
+impl<DB> ContractHarness<DB>
+where
+    DB: Backend,
+{
+    /// Runs the transactions of a block in order, as a Linera block of EVM
+    /// transactions, and commits them, the failed ones included, once the
+    /// whole block is valid.
+    ///
+    /// The block environment of the harness is set to `block`. With the
+    /// automatic nonces, the nonces of the transactions are replaced by the
+    /// tracked ones. A transaction whose gas limit exceeds the gas left in
+    /// the block, or which is rejected by revm, makes the block invalid: an
+    /// error is returned and nothing is committed. The transactions are
+    /// executed on a `CacheDB` over the database, so that each one sees the
+    /// changes of the previous ones, and their changes are committed as one,
+    /// so that a commit rejected by the database, for example for a
+    /// `QuotaError`, also leaves nothing behind.
+    pub fn execute_block(&mut self, block: BlockEnv, transactions: Vec<TxEnv>) -> anyhow::Result<BlockOutcome> {
+        let span = info_span!("execute_block", number = block.number, transactions = transactions.len());
+        let _guard = span.enter();
+        let block_gas_limit = block.gas_limit;
+        self.set_block(block);
+        let mut overlay = ContractHarness::new(CacheDB::new(self.db()), self.spec_id());
+        overlay.set_block(self.block().clone());
+        overlay.set_auto_nonce(self.auto_nonce());
+        let mut states = Vec::new();
+        let mut receipts = Vec::new();
+        let mut cumulative_gas_used = 0;
+        for (index, mut tx) in transactions.into_iter().enumerate() {
+            let gas_left = block_gas_limit - cumulative_gas_used;
+            if tx.gas_limit > gas_left {
+                anyhow::bail!(
+                    "The transaction {index} has a gas limit of {} while {gas_left} gas is left in the block",
+                    tx.gas_limit
+                );
+            }
+            if overlay.auto_nonce() {
+                tx.nonce = overlay.next_nonce(tx.caller)?;
+            }
+            let tx_type = tx.tx_type;
+            let result_and_state = overlay
+                .transact_tx(tx)
+                .with_context(|| format!("The transaction {index} is invalid"))?;
+            overlay.commit(result_and_state.state.clone())?;
+            states.push(result_and_state.state);
+            let result = result_and_state.result;
+            let gas_used = result.gas_used();
+            cumulative_gas_used += gas_used;
+            let contract_address = match &result {
+                ExecutionResult::Success {
+                    output: Output::Create(_, contract_address),
+                    ..
+                } => *contract_address,
+                _ => None,
+            };
+            let success = result.is_success();
+            let logs = result.into_logs();
+            receipts.push(BlockReceipt {
+                tx_type,
+                success,
+                gas_used,
+                cumulative_gas_used,
+                logs_bloom: logs_bloom(&logs),
+                logs,
+                contract_address,
+            });
+        }
+        drop(overlay);
+        let mut merged = EvmState::default();
+        for state in states {
+            merge_state(&mut merged, state);
+        }
+        self.commit(merged)?;
+        let mut logs_bloom = Bloom::default();
+        for receipt in &receipts {
+            logs_bloom.accrue_bloom(&receipt.logs_bloom);
+        }
+        let envelopes = receipts.iter().map(BlockReceipt::envelope).collect::<Vec<_>>();
+        let receipts_root = calculate_receipt_root(&envelopes);
+        info!(gas_used = cumulative_gas_used, %receipts_root, "block executed");
+        Ok(BlockOutcome {
+            receipts,
+            gas_used: cumulative_gas_used,
+            logs_bloom,
+            receipts_root,
+        })
+    }
+}
+
+/// Folds the changes of a transaction into the changes of the previous
+/// transactions of the block: the storage slots keep their value from
+/// before the block as original value.
+fn merge_state(merged: &mut EvmState, state: EvmState) {
+    for (address, mut account) in state {
+        if !account.is_touched() {
+            continue;
+        }
+        let Some(previous) = merged.get_mut(&address) else {
+            merged.insert(address, account);
+            continue;
+        };
+        if account.is_selfdestructed() || account.is_created() {
+            // The storage of the account starts over.
+            *previous = account;
+        } else if previous.is_selfdestructed() {
+            // The account is re-created from an empty one.
+            account.mark_created();
+            *previous = account;
+        } else {
+            previous.info = account.info;
+            for (index, value) in account.storage {
+                match previous.storage.get_mut(&index) {
+                    Some(slot) => slot.present_value = value.present_value,
+                    None => {
+                        previous.storage.insert(index, value);
+                    }
+                }
+            }
+        }
+    }
+}
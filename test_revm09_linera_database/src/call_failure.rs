@@ -0,0 +1,144 @@
+use std::fmt;
+
+use alloy_dyn_abi::{DynSolValue, JsonAbiExt};
+use alloy_json_abi::JsonAbi;
+use alloy_sol_types::{Panic, Revert, SolError};
+use revm::primitives::Bytes;
+use revm_context::result::{ExecutionResult, HaltReason};
+use revm_primitives::U256;
+
+/// The decoded revert data of a call.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RevertReason {
+    /// `Error(string)`, as emitted by `require` and `revert("...")`.
+    Error(String),
+    /// `Panic(uint256)`, as emitted by failed assertions and by the checked
+    /// arithmetic.
+    Panic {
+        code: U256,
+        description: &'static str,
+    },
+    /// A custom error found by its selector in the ABI of the contract.
+    Custom {
+        name: String,
+        arguments: Vec<DynSolValue>,
+    },
+    /// Revert data that could not be decoded, for example the empty data of
+    /// a plain `revert()`.
+    Raw(Bytes),
+}
+
+impl RevertReason {
+    /// Decodes the revert data. The custom errors are looked up in the
+    /// given ABIs, in order.
+    pub fn decode<'a>(output: &[u8], abis: impl IntoIterator<Item = &'a JsonAbi>) -> Self {
+        if let Ok(revert) = Revert::abi_decode(output) {
+            return RevertReason::Error(revert.reason);
+        }
+        if let Ok(panic) = Panic::abi_decode(output) {
+            let description = panic
+                .kind()
+                .map(|kind| kind.as_str())
+                .unwrap_or("unknown panic code");
+            return RevertReason::Panic {
+                code: panic.code,
+                description,
+            };
+        }
+        if let Some((selector, data)) = output.split_first_chunk::<4>() {
+            for abi in abis {
+                for error in abi.errors() {
+                    if error.selector().0 != *selector {
+                        continue;
+                    }
+                    if let Ok(arguments) = error.abi_decode_input(data) {
+                        return RevertReason::Custom {
+                            name: error.name.clone(),
+                            arguments,
+                        };
+                    }
+                }
+            }
+        }
+        RevertReason::Raw(Bytes::copy_from_slice(output))
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Error(reason) => write!(f, "Error({reason:?})"),
+            RevertReason::Panic { code, description } => {
+                write!(f, "Panic({code:#x}): {description}")
+            }
+            RevertReason::Custom { name, arguments } => {
+                write!(f, "{name}(")?;
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{argument:?}")?;
+                }
+                write!(f, ")")
+            }
+            RevertReason::Raw(output) if output.is_empty() => write!(f, "empty revert data"),
+            RevertReason::Raw(output) => write!(f, "undecoded revert data {output}"),
+        }
+    }
+}
+
+/// Why a transaction did not succeed. It is the error returned by the
+/// calls of `ContractHarness` and can be recovered with `downcast_ref`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CallFailure {
+    Revert { reason: RevertReason, gas_used: u64 },
+    Halt { reason: HaltReason, gas_used: u64 },
+}
+
+impl CallFailure {
+    /// Returns the failure of a non-successful result, `None` for a success.
+    pub fn from_result<'a>(
+        result: &ExecutionResult,
+        abis: impl IntoIterator<Item = &'a JsonAbi>,
+    ) -> Option<Self> {
+        match result {
+            ExecutionResult::Success { .. } => None,
+            ExecutionResult::Revert { gas_used, output } => Some(CallFailure::Revert {
+                reason: RevertReason::decode(output, abis),
+                gas_used: *gas_used,
+            }),
+            ExecutionResult::Halt { reason, gas_used } => Some(CallFailure::Halt {
+                reason: *reason,
+                gas_used: *gas_used,
+            }),
+        }
+    }
+
+    pub fn gas_used(&self) -> u64 {
+        match self {
+            CallFailure::Revert { gas_used, .. } | CallFailure::Halt { gas_used, .. } => *gas_used,
+        }
+    }
+
+    pub fn revert_reason(&self) -> Option<&RevertReason> {
+        match self {
+            CallFailure::Revert { reason, .. } => Some(reason),
+            CallFailure::Halt { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for CallFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallFailure::Revert { reason, gas_used } => {
+                write!(f, "reverted with {reason} (gas used {gas_used})")
+            }
+            CallFailure::Halt { reason, gas_used } => {
+                write!(f, "halted with {reason:?} (gas used {gas_used})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CallFailure {}
@@ -0,0 +1,219 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use revm_context::{result::ExecutionResult, TxEnv};
+use revm_primitives::{hardfork::SpecId, Address, B256, U256};
+use revm_state::AccountInfo;
+use tracing::{info_span, warn};
+
+use crate::{backend::Backend, harness::ContractHarness};
+
+/// The parts of an `AccountInfo` that both databases have to agree on, an
+/// absent account being an empty one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountSummary {
+    pub balance: U256,
+    pub nonce: u64,
+    pub code_hash: B256,
+}
+
+impl From<Option<AccountInfo>> for AccountSummary {
+    fn from(info: Option<AccountInfo>) -> Self {
+        let info = info.unwrap_or_default();
+        Self {
+            balance: info.balance,
+            nonce: info.nonce,
+            code_hash: info.code_hash,
+        }
+    }
+}
+
+/// The first difference between the two databases, found after the
+/// transaction of index `transaction`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Divergence {
+    /// Only one of the databases rejected the transaction.
+    Rejection {
+        transaction: usize,
+        left: Option<String>,
+        right: Option<String>,
+    },
+    /// The results differ, which covers the status, the gas and the logs.
+    Result {
+        transaction: usize,
+        left: Box<ExecutionResult>,
+        right: Box<ExecutionResult>,
+    },
+    AccountInfo {
+        transaction: usize,
+        address: Address,
+        left: AccountSummary,
+        right: AccountSummary,
+    },
+    /// The slots whose values differ, as (slot, left value, right value).
+    Storage {
+        transaction: usize,
+        address: Address,
+        diff: Vec<(U256, U256, U256)>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Rejection { transaction, left, right } => {
+                writeln!(f, "transaction {transaction}: rejected by one database only")?;
+                writeln!(f, "  left: {left:?}")?;
+                write!(f, "  right: {right:?}")
+            }
+            Divergence::Result { transaction, left, right } => {
+                writeln!(f, "transaction {transaction}: the results differ")?;
+                writeln!(f, "  left: {left:?}")?;
+                write!(f, "  right: {right:?}")
+            }
+            Divergence::AccountInfo {
+                transaction,
+                address,
+                left,
+                right,
+            } => {
+                writeln!(f, "transaction {transaction}: the account {address} differs")?;
+                writeln!(f, "  left: {left:?}")?;
+                write!(f, "  right: {right:?}")
+            }
+            Divergence::Storage {
+                transaction,
+                address,
+                diff,
+            } => {
+                write!(f, "transaction {transaction}: the storage of {address} differs")?;
+                for (slot, left, right) in diff {
+                    write!(f, "\n  slot {slot}: left {left}, right {right}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Executes every transaction on two databases, typically an `InMemoryDB`
+/// and a `LineraDatabase`, and compares the results and, after the commits,
+/// the account infos and storages of the touched accounts.
+///
+/// The first divergence is returned as the error of `execute`, and can be
+/// recovered with `downcast_ref::<Divergence>`.
+pub struct DifferentialRunner<L, R> {
+    pub left: ContractHarness<L>,
+    pub right: ContractHarness<R>,
+    number_transactions: usize,
+}
+
+impl<L: Backend, R: Backend> DifferentialRunner<L, R> {
+    pub fn new(left: L, right: R, spec_id: SpecId) -> Self {
+        Self {
+            left: ContractHarness::new(left, spec_id),
+            right: ContractHarness::new(right, spec_id),
+            number_transactions: 0,
+        }
+    }
+
+    /// Adds the account to both databases, see `Backend::add_account`.
+    pub fn add_account(&mut self, address: Address, info: Option<AccountInfo>) -> anyhow::Result<()> {
+        self.left.db_mut().add_account(address, info.clone())?;
+        self.right.db_mut().add_account(address, info)
+    }
+
+    /// Executes and commits `tx` on both databases, the nonce being the
+    /// next one of the caller, and returns the result of the left one.
+    pub fn execute(&mut self, tx: TxEnv) -> anyhow::Result<ExecutionResult> {
+        let transaction = self.number_transactions;
+        self.number_transactions += 1;
+        let span = info_span!("differential", transaction);
+        let _guard = span.enter();
+        let left = execute(&mut self.left, tx.clone());
+        let right = execute(&mut self.right, tx);
+        let ((left, left_touched), (right, right_touched)) = match (left, right) {
+            (Ok(left), Ok(right)) => (left, right),
+            (Err(left), Err(_)) => return Err(left),
+            (left, right) => {
+                let divergence = Divergence::Rejection {
+                    transaction,
+                    left: left.err().map(|error| error.to_string()),
+                    right: right.err().map(|error| error.to_string()),
+                };
+                return Err(self.report(divergence));
+            }
+        };
+        if left != right {
+            let divergence = Divergence::Result {
+                transaction,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+            return Err(self.report(divergence));
+        }
+        let touched = left_touched.union(&right_touched).copied().collect::<BTreeSet<_>>();
+        for address in touched {
+            if let Some(divergence) = self.compare_account(transaction, address)? {
+                return Err(self.report(divergence));
+            }
+        }
+        Ok(left)
+    }
+
+    fn compare_account(&self, transaction: usize, address: Address) -> anyhow::Result<Option<Divergence>> {
+        let (Ok(left), Ok(right)) = (self.left.db().basic_ref(address), self.right.db().basic_ref(address)) else {
+            anyhow::bail!("The basic_ref failed for address={address}");
+        };
+        let (left, right) = (AccountSummary::from(left), AccountSummary::from(right));
+        if left != right {
+            return Ok(Some(Divergence::AccountInfo {
+                transaction,
+                address,
+                left,
+                right,
+            }));
+        }
+        let left = self.left.db().storage_slots(address)?;
+        let right = self.right.db().storage_slots(address)?;
+        if left == right {
+            return Ok(None);
+        }
+        let slots = left.keys().chain(right.keys()).copied().collect::<BTreeSet<_>>();
+        let value = |storage: &BTreeMap<U256, U256>, slot| storage.get(&slot).copied().unwrap_or_default();
+        let diff = slots
+            .into_iter()
+            .map(|slot| (slot, value(&left, slot), value(&right, slot)))
+            .filter(|(_, left, right)| left != right)
+            .collect();
+        Ok(Some(Divergence::Storage {
+            transaction,
+            address,
+            diff,
+        }))
+    }
+
+    fn report(&self, divergence: Divergence) -> anyhow::Error {
+        warn!("divergence between the databases:\n{divergence}");
+        divergence.into()
+    }
+}
+
+/// Executes and commits `tx`, returning the result and the touched
+/// addresses.
+fn execute<DB: Backend>(
+    harness: &mut ContractHarness<DB>,
+    mut tx: TxEnv,
+) -> anyhow::Result<(ExecutionResult, BTreeSet<Address>)> {
+    if harness.auto_nonce() {
+        tx.nonce = harness.next_nonce(tx.caller)?;
+    }
+    let result_and_state = harness.transact_tx(tx)?;
+    let touched = result_and_state.state.keys().copied().collect();
+    harness.commit(result_and_state.state)?;
+    Ok((result_and_state.result, touched))
+}
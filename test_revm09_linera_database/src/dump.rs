@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, fmt};
+
+use linera_views::store::TestKeyValueStore;
+use revm_database::AccountState;
+use revm_primitives::{Address, U256};
+use revm_state::AccountInfo;
+use serde::Serialize;
+
+use crate::{
+    key_format::{account_key_prefix, decode_key, DecodedKey},
+    linera_database::{from_bytes_option, LineraDatabase},
+    policy::StorageUsage,
+};
+
+/// Everything the store holds under the key prefix of one account.
+#[derive(Debug, Serialize)]
+pub struct AccountDump {
+    pub address: Address,
+    /// Whether the address is handled by the database. Entries for unknown
+    /// addresses are stale.
+    pub is_known: bool,
+    pub info: Option<AccountInfo>,
+    pub state: Option<AccountState>,
+    pub usage: Option<StorageUsage>,
+    pub storage: BTreeMap<U256, U256>,
+    /// The entries under the prefix of the account whose key cannot be
+    /// decoded, as hexadecimal key and value.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub undecoded: Vec<(String, String)>,
+}
+
+/// The accounts of the store, ordered by address.
+#[derive(Debug, Serialize)]
+pub struct StateDump {
+    pub accounts: Vec<AccountDump>,
+    /// The entries whose key is in no known layout, for example the legacy
+    /// keys of a store that was not migrated, as hexadecimal key and value.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub undecoded: Vec<(String, String)>,
+}
+
+impl StateDump {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for AccountDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_known {
+            writeln!(f, "account {}", self.address)?;
+        } else {
+            writeln!(f, "unknown account {}", self.address)?;
+        }
+        match &self.info {
+            Some(info) => {
+                let code_len = info.code.as_ref().map(|code| code.len()).unwrap_or_default();
+                writeln!(
+                    f,
+                    "  balance={} nonce={} code_hash={} |code|={}",
+                    info.balance, info.nonce, info.code_hash, code_len
+                )?;
+            }
+            None => writeln!(f, "  no account info")?,
+        }
+        writeln!(f, "  state={:?}", self.state)?;
+        if let Some(usage) = &self.usage {
+            writeln!(f, "  slots={} bytes={}", usage.slots, usage.bytes)?;
+        }
+        for (index, value) in &self.storage {
+            writeln!(f, "  storage[{}] = {}", index, value)?;
+        }
+        for (key, value) in &self.undecoded {
+            writeln!(f, "  undecoded key {} = {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StateDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for account in &self.accounts {
+            write!(f, "{}", account)?;
+        }
+        for (key, value) in &self.undecoded {
+            writeln!(f, "undecoded key {} = {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn raw_entry(key: &[u8], value: &[u8]) -> (String, String) {
+    (hex::encode(key), hex::encode(value))
+}
+
+fn insert_key_value(
+    account: &mut AccountDump,
+    key: &[u8],
+    value: Vec<u8>,
+) -> Result<(), bcs::Error> {
+    let Some(decoded_key) = decode_key(key) else {
+        account.undecoded.push(raw_entry(key, &value));
+        return Ok(());
+    };
+    let value = Some(value);
+    match decoded_key {
+        DecodedKey::AccountInfo(_) => {
+            account.info = from_bytes_option::<AccountInfo>(&value)?;
+        }
+        DecodedKey::AccountState(_) => {
+            account.state = from_bytes_option::<AccountState>(&value)?;
+        }
+        DecodedKey::Storage(_, index) => {
+            let value = from_bytes_option::<U256>(&value)?.unwrap_or_default();
+            account.storage.insert(index, value);
+        }
+        DecodedKey::StorageUsage(_) => {
+            account.usage = from_bytes_option::<StorageUsage>(&value)?;
+        }
+    }
+    Ok(())
+}
+
+impl<C> LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: 'static,
+{
+    /// Dumps the account info, state and storage of `address` as found in
+    /// the store, bypassing the cache of `StorageStats`.
+    pub fn dump_account(&self, address: Address) -> Result<AccountDump, C::Error> {
+        let key_prefix = account_key_prefix(&address);
+        let mut account = self.empty_account_dump(address);
+        for (key, value) in self.find_key_values_by_prefix(&key_prefix)? {
+            let key = [key_prefix.as_slice(), &key].concat();
+            insert_key_value(&mut account, &key, value)?;
+        }
+        Ok(account)
+    }
+
+    /// Dumps every account present in the store, including the ones whose
+    /// address is not handled by the database. The keys that cannot be
+    /// decoded are listed in `undecoded`.
+    pub fn dump_all(&self) -> Result<StateDump, C::Error> {
+        let mut accounts = BTreeMap::<Address, AccountDump>::new();
+        let mut undecoded = Vec::new();
+        for (key, value) in self.find_key_values_by_prefix(&[])? {
+            let Some(decoded_key) = decode_key(&key) else {
+                undecoded.push(raw_entry(&key, &value));
+                continue;
+            };
+            let address = match decoded_key {
+                DecodedKey::AccountInfo(address)
+                | DecodedKey::AccountState(address)
+                | DecodedKey::Storage(address, _)
+                | DecodedKey::StorageUsage(address) => address,
+            };
+            let account = accounts
+                .entry(address)
+                .or_insert_with(|| self.empty_account_dump(address));
+            insert_key_value(account, &key, value)?;
+        }
+        Ok(StateDump {
+            accounts: accounts.into_values().collect(),
+            undecoded,
+        })
+    }
+
+    fn empty_account_dump(&self, address: Address) -> AccountDump {
+        AccountDump {
+            address,
+            is_known: self.is_known_address(&address),
+            info: None,
+            state: None,
+            usage: None,
+            storage: BTreeMap::new(),
+            undecoded: Vec::new(),
+        }
+    }
+}
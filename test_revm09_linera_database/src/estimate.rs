@@ -0,0 +1,107 @@
+use revm_context::{
+    result::{ExecutionResult, InvalidTransaction},
+    TxEnv,
+};
+use revm_primitives::U256;
+use tracing::{debug, info_span};
+
+use crate::{backend::Backend, harness::ContractHarness};
+
+/// The gas that a CALL with value gives to the callee on top of the
+/// forwarded gas.
+const CALL_STIPEND: u64 = 2300;
+
+impl<DB> ContractHarness<DB>
+where
+    DB: Backend,
+{
+    /// Executes `tx`, with its nonce as given, and discards the state
+    /// changes, as `eth_call`. The transactions rejected by revm, for example
+    /// with a gas limit below the intrinsic gas, return an error.
+    pub fn simulate(&mut self, tx: TxEnv) -> anyhow::Result<ExecutionResult> {
+        Ok(self.transact_tx(tx)?.result)
+    }
+
+    /// Returns the smallest gas limit for which `tx` succeeds, as
+    /// `eth_estimateGas`.
+    ///
+    /// The gas used by a successful execution is not enough in general: a
+    /// CALL forwards at most 63/64 of the gas left (EIP-150), so the caller
+    /// needs more gas than what the callee actually uses. The first guess
+    /// accounts for it and a binary search finds the minimum, which costs a
+    /// few dozen executions. The search starts from the gas limit of `tx`,
+    /// capped by the block gas limit and by what the caller can pay. If `tx`
+    /// fails with that gas, its `CallFailure` is returned.
+    pub fn estimate_gas(&mut self, tx: TxEnv) -> anyhow::Result<u64> {
+        let span = info_span!("estimate_gas", caller = %tx.caller, kind = ?tx.kind);
+        let _guard = span.enter();
+        let mut high = self.gas_cap(&tx)?;
+        let result = self.simulate(TxEnv {
+            gas_limit: high,
+            ..tx.clone()
+        })?;
+        let ExecutionResult::Success {
+            gas_used,
+            gas_refunded,
+            ..
+        } = result
+        else {
+            let contract_address = tx.kind.to().copied();
+            let failure = self
+                .call_failure(contract_address, &result)
+                .expect("The result is a failure");
+            return Err(failure.into());
+        };
+        // The gas used is net of the refund, so any lower limit fails.
+        let mut low = gas_used.saturating_sub(1);
+        let optimistic = (gas_used + gas_refunded + CALL_STIPEND) * 64 / 63;
+        if optimistic < high {
+            if self.succeeds(&tx, optimistic)? {
+                high = optimistic;
+            } else {
+                low = optimistic;
+            }
+        }
+        while low + 1 < high {
+            // As in geth, the first tries stay close to the low end since
+            // the estimate is usually there.
+            let middle = ((low + high) / 2).min(low.saturating_mul(2).max(low + 1));
+            if self.succeeds(&tx, middle)? {
+                high = middle;
+            } else {
+                low = middle;
+            }
+        }
+        debug!(gas_used, estimate = high, "gas estimated");
+        Ok(high)
+    }
+
+    fn succeeds(&mut self, tx: &TxEnv, gas_limit: u64) -> anyhow::Result<bool> {
+        let tx = TxEnv {
+            gas_limit,
+            ..tx.clone()
+        };
+        match self.simulate(tx) {
+            Ok(result) => Ok(result.is_success()),
+            // A gas limit below the intrinsic gas is rejected before
+            // execution, the other errors do not depend on the gas.
+            Err(error) if error.downcast_ref::<InvalidTransaction>().is_some() => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// The largest gas limit that `tx` can use.
+    fn gas_cap(&self, tx: &TxEnv) -> anyhow::Result<u64> {
+        let mut cap = tx.gas_limit.min(self.block().gas_limit);
+        if tx.gas_price > 0 {
+            let info = self
+                .db()
+                .basic_ref(tx.caller)
+                .map_err(|error| anyhow::anyhow!("The basic_ref failed for address={}: {error}", tx.caller))?;
+            let balance = info.map(|info| info.balance).unwrap_or_default();
+            let affordable = balance.saturating_sub(tx.value) / U256::from(tx.gas_price);
+            cap = cap.min(affordable.saturating_to::<u64>());
+        }
+        Ok(cap)
+    }
+}
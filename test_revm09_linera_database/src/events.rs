@@ -0,0 +1,71 @@
+use std::fmt;
+
+use alloy_dyn_abi::{DynSolValue, EventExt};
+use alloy_json_abi::JsonAbi;
+use revm_primitives::{Address, Log};
+
+/// A log decoded against the ABI of a contract.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedEvent {
+    pub address: Address,
+    pub name: String,
+    /// The parameters in the order of the declaration of the event, the
+    /// indexed ones included. The indexed parameters of dynamic type are
+    /// only known by their hash.
+    pub params: Vec<(String, DynSolValue)>,
+}
+
+impl DecodedEvent {
+    pub fn param(&self, name: &str) -> Option<&DynSolValue> {
+        self.params
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value)
+    }
+}
+
+impl fmt::Display for DecodedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (index, (name, value)) in self.params.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{name}: {value:?}")?;
+        }
+        write!(f, ") from {}", self.address)
+    }
+}
+
+/// Decodes a log with the first of the ABIs having an event of matching
+/// signature. The anonymous events are not decoded since they cannot be
+/// identified.
+pub fn decode_log<'a>(log: &Log, abis: impl IntoIterator<Item = &'a JsonAbi>) -> Option<DecodedEvent> {
+    let selector = log.data.topics().first()?;
+    for abi in abis {
+        for event in abi.events() {
+            if event.anonymous || event.selector() != *selector {
+                continue;
+            }
+            let Ok(decoded) = event.decode_log(&log.data) else {
+                continue;
+            };
+            let mut indexed = decoded.indexed.into_iter();
+            let mut body = decoded.body.into_iter();
+            let params = event
+                .inputs
+                .iter()
+                .map(|input| {
+                    let value = if input.indexed { indexed.next() } else { body.next() };
+                    (input.name.clone(), value.expect("The decoded values match the inputs"))
+                })
+                .collect();
+            return Some(DecodedEvent {
+                address: log.address,
+                name: event.name.clone(),
+                params,
+            });
+        }
+    }
+    None
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use futures::executor::block_on;
+use linera_views::{memory::MemoryStore, store::TestKeyValueStore};
+use proptest::{
+    prelude::*,
+    test_runner::{Config, TestCaseError, TestError, TestRunner},
+};
+use revm::primitives::Bytes;
+use revm_primitives::{hardfork::SpecId, keccak256, Address, U256};
+use test_linera_database::{
+    harness::ContractHarness,
+    linera_database::LineraDatabase,
+    logging::init_tracing,
+    workloads::{key_value_map_bytecode, Operation},
+};
+use tracing::info;
+
+/// The keys and values are drawn from small ranges so that the sequences
+/// overwrite, delete and read back the same slots, zero values included.
+const MAX_KEY: u8 = 4;
+const MAX_VALUE: u8 = 4;
+
+fn operation_strategy() -> impl Strategy<Value = Operation> {
+    let key = (0..MAX_KEY).prop_map(U256::from);
+    let value = (0..MAX_VALUE).prop_map(U256::from);
+    prop_oneof![
+        (key.clone(), value.clone()).prop_map(|(key, value)| Operation::InsertKeyValue(key, value)),
+        (key.clone(), value).prop_map(|(key, value)| Operation::InsertKeyValueBis(key, value)),
+        key.clone().prop_map(Operation::DeleteKey),
+        key.prop_map(Operation::ReadValue),
+    ]
+}
+
+/// The slot of `map[key]` for the mapping in slot 0.
+fn map_slot(key: U256) -> U256 {
+    let preimage = [key.to_be_bytes::<32>(), [0; 32]].concat();
+    U256::from_be_bytes(keccak256(preimage).0)
+}
+
+/// Runs `operations` on a fresh `LineraDatabase` and checks the returned
+/// values and, after every operation, the persisted storage against a
+/// `BTreeMap` model.
+fn check_operations(bytecode: &Bytes, operations: &[Operation]) -> anyhow::Result<()> {
+    let db = block_on(MemoryStore::new_test_store()).unwrap();
+    let db = LineraDatabase::new(db);
+    let mut harness = ContractHarness::new(db, SpecId::CANCUN);
+    let contract_address = harness.deploy(bytecode.clone())?;
+    anyhow::ensure!(contract_address == Address::ZERO.create(0), "unexpected address {contract_address}");
+    let mut model = BTreeMap::<U256, U256>::new();
+    for (index, operation) in operations.iter().enumerate() {
+        let output = harness.call(contract_address, operation.abi_encode())?;
+        match *operation {
+            Operation::InsertKeyValue(key, value) => {
+                model.insert(key, value);
+            }
+            Operation::InsertKeyValueBis(key, value) => {
+                model.insert(key, value + U256::from(1));
+            }
+            Operation::DeleteKey(key) => {
+                model.remove(&key);
+            }
+            Operation::ReadValue(key) => {
+                let expected = model.get(&key).copied().unwrap_or_default();
+                let value = U256::from_be_slice(&output);
+                anyhow::ensure!(
+                    value == expected,
+                    "operation {index}: read {value} for key {key} instead of {expected}"
+                );
+            }
+        }
+        model.retain(|_, value| *value != U256::ZERO);
+
+        let account = harness.db().dump_account(contract_address)?;
+        let expected_storage = model
+            .iter()
+            .map(|(key, value)| (map_slot(*key), *value))
+            .collect::<BTreeMap<_, _>>();
+        anyhow::ensure!(
+            account.storage == expected_storage,
+            "operation {index}: the storage is {:?} instead of {expected_storage:?}",
+            account.storage
+        );
+        let usage = account.usage.context("The usage should be stored")?;
+        anyhow::ensure!(
+            usage.slots == model.len() as u64,
+            "operation {index}: the usage counts {} slots instead of {}",
+            usage.slots,
+            model.len()
+        );
+    }
+    Ok(())
+}
+
+/// Fuzzes the `ExampleKeyValueMap` with random sequences of operations. On
+/// failure, proptest shrinks the sequence to a minimal one, which is
+/// printed. The number of cases is set by `PROPTEST_CASES`.
+fn main() -> anyhow::Result<()> {
+    init_tracing();
+    let bytecode = key_value_map_bytecode()?;
+    let config = Config {
+        failure_persistence: None,
+        ..Config::default()
+    };
+    let cases = config.cases;
+    let mut runner = TestRunner::new(config);
+    let strategy = proptest::collection::vec(operation_strategy(), 1..40);
+    let result = runner.run(&strategy, |operations| {
+        check_operations(&bytecode, &operations).map_err(|error| TestCaseError::fail(format!("{error:#}")))
+    });
+    match result {
+        Ok(()) => {
+            info!(cases, "The model-based fuzzing has been successful");
+            Ok(())
+        }
+        Err(TestError::Fail(reason, operations)) => {
+            anyhow::bail!("Minimal failing sequence {operations:?}: {reason}")
+        }
+        Err(error) => anyhow::bail!("The fuzzing failed: {error}"),
+    }
+}
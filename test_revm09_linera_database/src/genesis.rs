@@ -0,0 +1,121 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use revm::bytecode::Bytecode;
+use revm_primitives::{Address, Bytes, KECCAK_EMPTY, U256};
+use revm_state::AccountInfo;
+use serde::{Deserialize, Serialize};
+use tracing::{info, info_span};
+
+use crate::backend::Backend;
+
+/// An account of the `alloc` of a geth genesis file. The balance and the
+/// nonce are hexadecimal strings (decimal ones are accepted too). The keys
+/// and the values of the storage are read as numbers, so that short ones
+/// like `"0x01"` are accepted, and written back as 32-byte words.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    pub balance: U256,
+    #[serde(default, skip_serializing_if = "U256::is_zero")]
+    pub nonce: U256,
+    #[serde(default, skip_serializing_if = "is_empty_code")]
+    pub code: Bytes,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty", with = "storage_words")]
+    pub storage: BTreeMap<U256, U256>,
+}
+
+pub type GenesisAlloc = BTreeMap<Address, GenesisAccount>;
+
+fn is_empty_code(code: &Bytes) -> bool {
+    code.is_empty()
+}
+
+/// The storage of a `GenesisAccount`, padded to 32-byte words when written.
+mod storage_words {
+    use std::collections::BTreeMap;
+
+    use revm_primitives::{B256, U256};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(storage: &BTreeMap<U256, U256>, serializer: S) -> Result<S::Ok, S::Error> {
+        storage
+            .iter()
+            .map(|(slot, value)| (B256::from(*slot), B256::from(*value)))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<U256, U256>, D::Error> {
+        BTreeMap::deserialize(deserializer)
+    }
+}
+
+/// Loads the alloc of `path`, which is either a full genesis file or only
+/// its `alloc` object.
+pub fn load_genesis_alloc(path: &Path) -> anyhow::Result<GenesisAlloc> {
+    let contents = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut value = serde_json::from_str::<serde_json::Value>(&contents)?;
+    if let Some(alloc) = value.get_mut("alloc") {
+        value = alloc.take();
+    }
+    serde_json::from_value(value).with_context(|| format!("failed to parse the alloc of {}", path.display()))
+}
+
+pub fn save_genesis_alloc(path: &Path, alloc: &GenesisAlloc) -> anyhow::Result<()> {
+    let contents = serde_json::to_string_pretty(alloc)?;
+    std::fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Creates the accounts of `alloc` in `db`, see `Backend::insert_account`.
+/// The accounts already present are replaced.
+pub fn import_genesis_alloc<DB: Backend>(db: &mut DB, alloc: &GenesisAlloc) -> anyhow::Result<()> {
+    let span = info_span!("import_genesis_alloc", accounts = alloc.len());
+    let _guard = span.enter();
+    for (address, account) in alloc {
+        let nonce = u64::try_from(account.nonce)
+            .ok()
+            .with_context(|| format!("The nonce of {address} does not fit in a u64"))?;
+        let bytecode = Bytecode::new_raw(account.code.clone());
+        let info = AccountInfo::new(account.balance, nonce, bytecode.hash_slow(), bytecode);
+        db.insert_account(*address, info, &account.storage)?;
+    }
+    info!("genesis alloc imported");
+    Ok(())
+}
+
+/// The accounts of `db` as a genesis alloc, the empty accounts without
+/// storage being left out. Importing it into an empty database gives back
+/// the same accounts.
+pub fn export_genesis_alloc<DB: Backend>(db: &DB) -> anyhow::Result<GenesisAlloc> {
+    let mut alloc = GenesisAlloc::new();
+    for address in db.addresses()? {
+        let Ok(info) = db.basic_ref(address) else {
+            anyhow::bail!("The basic_ref failed for address={address}");
+        };
+        let Some(info) = info else {
+            continue;
+        };
+        let storage = db.storage_slots(address)?;
+        if info.is_empty() && storage.is_empty() {
+            continue;
+        }
+        let code = match info.code {
+            Some(code) => code.original_bytes(),
+            None if info.code_hash == KECCAK_EMPTY => Bytes::new(),
+            None => {
+                let Ok(code) = db.code_by_hash_ref(info.code_hash) else {
+                    anyhow::bail!("The code_by_hash_ref failed for address={address}");
+                };
+                code.original_bytes()
+            }
+        };
+        let account = GenesisAccount {
+            balance: info.balance,
+            nonce: U256::from(info.nonce),
+            code,
+            storage,
+        };
+        alloc.insert(address, account);
+    }
+    Ok(alloc)
+}
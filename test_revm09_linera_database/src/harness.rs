@@ -0,0 +1,452 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alloy_dyn_abi::DynSolValue;
+use alloy_json_abi::JsonAbi;
+use alloy_sol_types::SolCall;
+use futures::executor::block_on;
+use linera_views::{memory::MemoryStore, store::TestKeyValueStore};
+use revm::{primitives::Bytes, ExecuteEvm};
+use revm_context::{
+    result::{EVMError, ExecutionResult, Output, ResultAndState},
+    BlockEnv, Evm, Journal, TxEnv,
+};
+use revm_database::WrapDatabaseRef;
+use revm_handler::{instructions::EthInstructions, EthPrecompiles};
+use revm_primitives::{hardfork::SpecId, Address, Log, TxKind, B256};
+use revm_state::EvmState;
+use tracing::{debug, info_span};
+
+use crate::{
+    backend::Backend,
+    call_failure::CallFailure,
+    events::{decode_log, DecodedEvent},
+    linera_database::LineraDatabase,
+    metrics::StorageMetrics,
+    tx_builder::TxBuilder,
+};
+
+/// The outcome of a successful call.
+#[derive(Clone, Debug)]
+pub struct CallReceipt {
+    pub output: Bytes,
+    pub gas_used: u64,
+    pub logs: Vec<Log>,
+    /// The logs that could be decoded with the known ABIs, in order.
+    pub events: Vec<DecodedEvent>,
+}
+
+impl CallReceipt {
+    pub fn events_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a DecodedEvent> {
+        self.events.iter().filter(move |event| event.name == name)
+    }
+
+    /// Returns the first event called `name` whose parameters include
+    /// `params`, or an error listing the emitted events.
+    pub fn expect_event<'a>(
+        &'a self,
+        name: &str,
+        params: &[(&str, DynSolValue)],
+    ) -> anyhow::Result<&'a DecodedEvent> {
+        let event = self.events.iter().find(|event| {
+            event.name == name
+                && params
+                    .iter()
+                    .all(|(param_name, value)| event.param(param_name) == Some(value))
+        });
+        match event {
+            Some(event) => Ok(event),
+            None => {
+                let emitted = self.events.iter().map(ToString::to_string).collect::<Vec<_>>();
+                anyhow::bail!("No event {name} with {params:?}, the emitted events are {emitted:?}")
+            }
+        }
+    }
+
+    pub fn expect_no_event(&self, name: &str) -> anyhow::Result<()> {
+        if let Some(event) = self.events_named(name).next() {
+            anyhow::bail!("Unexpected event {event}");
+        }
+        Ok(())
+    }
+}
+
+/// Deploys and calls contracts against a database that it owns.
+///
+/// The transactions are sent by the deployer account unless another caller
+/// is given to `tx`. The nonce checks of revm 22+ are active, so the harness
+/// manages the nonce of every sender: it is read from the `AccountInfo` of
+/// the database on the first transaction and then follows the committed
+/// state changes. `set_auto_nonce(false)` disables this, the nonce given to
+/// `TxBuilder::nonce` (0 by default) is then used as is.
+///
+/// The transactions are executed in the block environment `block`, which
+/// starts as `BlockEnv::default()` and is changed between transactions with
+/// `roll`, `warp`, `skip`, `next_block` and the setters. A non-zero basefee
+/// requires a gas price from `TxBuilder::gas_price` or `eip1559_fees`.
+///
+/// The failed calls return a `CallFailure`. The custom errors are decoded
+/// with the ABIs given to `deploy_with_abi` or `set_abi`.
+pub struct ContractHarness<DB> {
+    db: DB,
+    spec_id: SpecId,
+    block: BlockEnv,
+    deployer: Address,
+    abis: HashMap<Address, JsonAbi>,
+    auto_nonce: bool,
+    nonces: HashMap<Address, u64>,
+}
+
+impl<DB> ContractHarness<DB>
+where
+    DB: Backend,
+{
+    /// Creates a harness whose deployer is `Address::ZERO`.
+    pub fn new(db: DB, spec_id: SpecId) -> Self {
+        Self::with_deployer(db, spec_id, Address::ZERO)
+    }
+
+    pub fn with_deployer(db: DB, spec_id: SpecId, deployer: Address) -> Self {
+        Self {
+            db,
+            spec_id,
+            block: BlockEnv::default(),
+            deployer,
+            abis: HashMap::new(),
+            auto_nonce: true,
+            nonces: HashMap::new(),
+        }
+    }
+
+    pub fn db(&self) -> &DB {
+        &self.db
+    }
+
+    pub fn db_mut(&mut self) -> &mut DB {
+        &mut self.db
+    }
+
+    pub fn into_db(self) -> DB {
+        self.db
+    }
+
+    pub fn spec_id(&self) -> SpecId {
+        self.spec_id
+    }
+
+    pub fn set_spec_id(&mut self, spec_id: SpecId) {
+        self.spec_id = spec_id;
+    }
+
+    pub fn block(&self) -> &BlockEnv {
+        &self.block
+    }
+
+    pub fn block_mut(&mut self) -> &mut BlockEnv {
+        &mut self.block
+    }
+
+    pub fn set_block(&mut self, block: BlockEnv) {
+        self.block = block;
+    }
+
+    /// Sets the block number.
+    pub fn roll(&mut self, number: u64) {
+        self.block.number = number;
+    }
+
+    /// Sets the block timestamp.
+    pub fn warp(&mut self, timestamp: u64) {
+        self.block.timestamp = timestamp;
+    }
+
+    /// Advances the block timestamp by `seconds`.
+    pub fn skip(&mut self, seconds: u64) -> anyhow::Result<()> {
+        let Some(timestamp) = self.block.timestamp.checked_add(seconds) else {
+            anyhow::bail!("The timestamp {} cannot be advanced by {seconds}", self.block.timestamp);
+        };
+        self.block.timestamp = timestamp;
+        Ok(())
+    }
+
+    /// Moves to the next block, `seconds` after the current one. The block
+    /// is left unchanged if its number or its timestamp would overflow.
+    pub fn next_block(&mut self, seconds: u64) -> anyhow::Result<()> {
+        let Some(number) = self.block.number.checked_add(1) else {
+            anyhow::bail!("The block number {} is the last one", self.block.number);
+        };
+        self.skip(seconds)?;
+        self.block.number = number;
+        Ok(())
+    }
+
+    pub fn set_coinbase(&mut self, coinbase: Address) {
+        self.block.beneficiary = coinbase;
+    }
+
+    pub fn set_basefee(&mut self, basefee: u64) {
+        self.block.basefee = basefee;
+    }
+
+    pub fn set_prevrandao(&mut self, prevrandao: B256) {
+        self.block.prevrandao = Some(prevrandao);
+    }
+
+    pub fn set_block_gas_limit(&mut self, gas_limit: u64) {
+        self.block.gas_limit = gas_limit;
+    }
+
+    pub fn deployer(&self) -> Address {
+        self.deployer
+    }
+
+    pub fn set_abi(&mut self, contract_address: Address, abi: JsonAbi) {
+        self.abis.insert(contract_address, abi);
+    }
+
+    pub fn abi(&self, contract_address: &Address) -> Option<&JsonAbi> {
+        self.abis.get(contract_address)
+    }
+
+    /// Turns a failed result into a `CallFailure`. The ABI of the called
+    /// contract is tried first, the reverts of nested calls being decoded
+    /// with the other known ABIs.
+    pub fn call_failure(&self, contract_address: Option<Address>, result: &ExecutionResult) -> Option<CallFailure> {
+        let own_abi = contract_address.and_then(|address| self.abis.get(&address));
+        let other_abis = self
+            .abis
+            .iter()
+            .filter(move |(address, _)| Some(**address) != contract_address)
+            .map(|(_, abi)| abi);
+        CallFailure::from_result(result, own_abi.into_iter().chain(other_abis))
+    }
+
+    /// Decodes the logs with the ABI of the emitting contract first.
+    pub fn decode_logs(&self, logs: &[Log]) -> Vec<DecodedEvent> {
+        logs.iter()
+            .filter_map(|log| {
+                let own_abi = self.abis.get(&log.address);
+                let other_abis = self
+                    .abis
+                    .iter()
+                    .filter(|(address, _)| **address != log.address)
+                    .map(|(_, abi)| abi);
+                decode_log(log, own_abi.into_iter().chain(other_abis))
+            })
+            .collect()
+    }
+
+    pub fn auto_nonce(&self) -> bool {
+        self.auto_nonce
+    }
+
+    pub fn set_auto_nonce(&mut self, auto_nonce: bool) {
+        self.auto_nonce = auto_nonce;
+    }
+
+    /// Returns the nonce of the next transaction of `caller`.
+    pub fn next_nonce(&mut self, caller: Address) -> anyhow::Result<u64> {
+        if let Some(nonce) = self.nonces.get(&caller) {
+            return Ok(*nonce);
+        }
+        let nonce = self.nonce(caller)?;
+        self.nonces.insert(caller, nonce);
+        Ok(nonce)
+    }
+
+    /// Forgets the tracked nonces, which are read again from the database.
+    /// This is needed when the database is committed to directly, without
+    /// `commit`.
+    pub fn reset_nonces(&mut self) {
+        self.nonces.clear();
+    }
+
+    /// The nonce of `address` as found in the database.
+    pub fn nonce(&self, address: Address) -> anyhow::Result<u64> {
+        let info = self
+            .db
+            .basic_ref(address)
+            .map_err(|error| anyhow::anyhow!("The basic_ref failed for address={address}: {error}"))?;
+        Ok(info.map(|info| info.nonce).unwrap_or_default())
+    }
+
+    /// Executes a transaction without committing it. The state changes
+    /// can be committed later with `commit`, which is how the transactions
+    /// run speculatively are handled.
+    pub fn transact(
+        &mut self,
+        caller: Address,
+        kind: TxKind,
+        data: Bytes,
+    ) -> anyhow::Result<ResultAndState> {
+        self.tx().caller(caller).transact(kind, data)
+    }
+
+    /// Executes `tx`, with its nonce as given, without committing it.
+    pub fn transact_tx(&mut self, tx: TxEnv) -> anyhow::Result<ResultAndState> {
+        let span = info_span!(
+            "transaction",
+            spec_id = ?self.spec_id,
+            block_number = self.block.number,
+            timestamp = self.block.timestamp,
+            caller = %tx.caller,
+            nonce = tx.nonce,
+            kind = ?tx.kind,
+            data_len = tx.data.len(),
+            gas_limit = tx.gas_limit,
+        );
+        let _guard = span.enter();
+        let mut ctx: revm_context::Context<BlockEnv, _, _, _, Journal<WrapDatabaseRef<&mut DB>>, ()> =
+            revm_context::Context::new(WrapDatabaseRef(&mut self.db), self.spec_id);
+        ctx.block = self.block.clone();
+        let instructions = EthInstructions::new_mainnet();
+        let mut evm = Evm::new(ctx, instructions, EthPrecompiles::default());
+
+        let result = evm.transact(tx);
+        let result_and_state = match result {
+            Ok(result_and_state) => result_and_state,
+            // The `InvalidTransaction` and the `InvalidHeader` are kept for
+            // `downcast_ref`, so that the rejections can be told from the
+            // errors of the database.
+            Err(EVMError::Transaction(error)) => {
+                let message = format!("The transact failed: Transaction({error:?})");
+                return Err(anyhow::Error::new(error).context(message));
+            }
+            Err(EVMError::Header(error)) => {
+                let message = format!("The transact failed: Header({error:?})");
+                return Err(anyhow::Error::new(error).context(message));
+            }
+            Err(error) => anyhow::bail!("The transact failed: {error:?}"),
+        };
+        debug!(result = ?result_and_state.result, "transaction executed");
+        Ok(result_and_state)
+    }
+
+    /// Commits the state changes of a transaction. A commit rejected by
+    /// the database, for example a `QuotaError` of the `LineraDatabase`,
+    /// is returned and leaves the tracked nonces unchanged.
+    pub fn commit(&mut self, state: EvmState) -> anyhow::Result<()> {
+        let nonces = state
+            .iter()
+            .map(|(address, account)| (*address, account.info.nonce))
+            .collect::<Vec<_>>();
+        self.db.try_commit(state)?;
+        for (address, nonce) in nonces {
+            if let Some(tracked) = self.nonces.get_mut(&address) {
+                *tracked = nonce;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts a transaction from the deployer with the default environment.
+    pub fn tx(&mut self) -> TxBuilder<'_, DB> {
+        let caller = self.deployer;
+        TxBuilder::new(self, caller)
+    }
+
+    /// Deploys a contract and returns its address.
+    pub fn deploy(&mut self, bytecode: Bytes) -> anyhow::Result<Address> {
+        self.tx().deploy(bytecode)
+    }
+
+    /// Deploys a contract whose custom errors and events are decoded with
+    /// `abi`.
+    pub fn deploy_with_abi(&mut self, bytecode: Bytes, abi: JsonAbi) -> anyhow::Result<Address> {
+        let contract_address = self.deploy(bytecode)?;
+        self.set_abi(contract_address, abi);
+        Ok(contract_address)
+    }
+
+    pub(crate) fn deploy_tx(&mut self, tx: TxEnv) -> anyhow::Result<Address> {
+        let ResultAndState { result, state } = self.transact_tx(tx)?;
+        self.commit(state)?;
+        if let Some(failure) = self.call_failure(None, &result) {
+            return Err(failure.into());
+        }
+        let ExecutionResult::Success { output, .. } = result else {
+            unreachable!("The failures have been handled");
+        };
+        let Output::Create(_, Some(contract_address)) = output else {
+            anyhow::bail!("The deployment did not return a contract address");
+        };
+        Ok(contract_address)
+    }
+
+    /// Calls a contract and commits the state changes, also for a failed
+    /// call since the nonce and the gas are then still charged.
+    pub fn call(&mut self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<Bytes> {
+        self.tx().call(contract_address, encoded_args)
+    }
+
+    /// Same as `call`, also returning the gas used and the emitted logs.
+    pub fn call_receipt(&mut self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<CallReceipt> {
+        self.tx().call_receipt(contract_address, encoded_args)
+    }
+
+    /// Calls a contract without committing anything, as `eth_call`.
+    pub fn static_call(&mut self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<Bytes> {
+        self.tx().static_call(contract_address, encoded_args)
+    }
+
+    pub fn static_call_receipt(&mut self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<CallReceipt> {
+        self.tx().static_call_receipt(contract_address, encoded_args)
+    }
+
+    pub(crate) fn call_receipt_tx(&mut self, tx: TxEnv, commit: bool) -> anyhow::Result<CallReceipt> {
+        let TxKind::Call(contract_address) = tx.kind else {
+            anyhow::bail!("A call needs a contract address");
+        };
+        let ResultAndState { result, state } = self.transact_tx(tx)?;
+        if commit {
+            self.commit(state)?;
+        }
+        self.receipt(contract_address, result)
+    }
+
+    /// Returns the output of a successful call, the `CallFailure` otherwise.
+    pub fn call_output(&self, contract_address: Address, result: ExecutionResult) -> anyhow::Result<Bytes> {
+        Ok(self.receipt(contract_address, result)?.output)
+    }
+
+    /// Returns the receipt of a successful call, the `CallFailure` otherwise.
+    pub fn receipt(&self, contract_address: Address, result: ExecutionResult) -> anyhow::Result<CallReceipt> {
+        if let Some(failure) = self.call_failure(Some(contract_address), &result) {
+            return Err(failure.into());
+        }
+        let ExecutionResult::Success { output, gas_used, logs, .. } = result else {
+            unreachable!("The failures have been handled");
+        };
+        let Output::Call(output) = output else {
+            anyhow::bail!("Only alternative is contract creation which is kind of unlikely")
+        };
+        let events = self.decode_logs(&logs);
+        Ok(CallReceipt {
+            output,
+            gas_used,
+            logs,
+            events,
+        })
+    }
+
+    /// Calls a contract with the arguments of `call`, commits and decodes
+    /// the returned values.
+    pub fn call_typed<C: SolCall>(&mut self, contract_address: Address, call: &C) -> anyhow::Result<C::Return> {
+        self.tx().call_typed(contract_address, call)
+    }
+
+    /// Same as `call_typed` without committing anything, which makes it
+    /// the typed `eth_call`.
+    pub fn static_call_typed<C: SolCall>(&mut self, contract_address: Address, call: &C) -> anyhow::Result<C::Return> {
+        self.tx().static_call_typed(contract_address, call)
+    }
+}
+
+impl ContractHarness<LineraDatabase<MemoryStore>> {
+    /// Creates a harness over a `LineraDatabase` on a fresh `MemoryStore`,
+    /// reporting to `metrics`, as used by the scenarios.
+    pub fn new_linera(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<Self> {
+        let store = block_on(MemoryStore::new_test_store())?;
+        let db = LineraDatabase::with_metrics(store, metrics.clone());
+        Ok(Self::new(db, spec_id))
+    }
+}
@@ -0,0 +1,238 @@
+use std::path::Path;
+
+use anyhow::Context;
+use futures::executor::block_on;
+use linera_views::{batch::Batch, store::{KeyValueIterable, TestKeyValueStore}};
+use revm_primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+
+/// The version of the key layout of the `LineraDatabase`.
+///
+/// Every key starts with this byte, followed by the 20 bytes of the address
+/// and a `KeyCategory`. The storage keys then carry the slot as 32 big-endian
+/// bytes, so that the order of the keys in the store is the numeric order of
+/// the slots of a contract.
+///
+/// The legacy layout was `[KeyTag, KeyCategory, bcs(slot)]`, see
+/// `migrate_store`. Its slots were big-endian too, the BCS encoding of a
+/// `U256` being a length byte followed by the 32 big-endian bytes, but the
+/// one byte `KeyTag` only covered a fixed set of addresses. The version is
+/// 0xFF so that it stays apart from the tags of the legacy keys, which
+/// start at 0 and only reach 0xFF with 253 registered contracts.
+pub const KEY_FORMAT_VERSION: u8 = 0xFF;
+
+const ADDRESS_LEN: usize = 20;
+
+#[repr(u8)]
+pub enum KeyCategory {
+    AccountInfo,
+    AccountState,
+    Storage,
+    StorageUsage,
+}
+
+/// A key of the store, decoded according to `KEY_FORMAT_VERSION`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodedKey {
+    AccountInfo(Address),
+    AccountState(Address),
+    Storage(Address, U256),
+    StorageUsage(Address),
+}
+
+pub fn account_key_prefix(address: &Address) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + ADDRESS_LEN);
+    key.push(KEY_FORMAT_VERSION);
+    key.extend_from_slice(address.as_slice());
+    key
+}
+
+pub fn account_info_key(address: &Address) -> Vec<u8> {
+    let mut key = account_key_prefix(address);
+    key.push(KeyCategory::AccountInfo as u8);
+    key
+}
+
+pub fn account_state_key(address: &Address) -> Vec<u8> {
+    let mut key = account_key_prefix(address);
+    key.push(KeyCategory::AccountState as u8);
+    key
+}
+
+pub fn storage_usage_key(address: &Address) -> Vec<u8> {
+    let mut key = account_key_prefix(address);
+    key.push(KeyCategory::StorageUsage as u8);
+    key
+}
+
+pub fn storage_key_prefix(address: &Address) -> Vec<u8> {
+    let mut key = account_key_prefix(address);
+    key.push(KeyCategory::Storage as u8);
+    key
+}
+
+pub fn storage_key(address: &Address, index: U256) -> Vec<u8> {
+    let mut key = storage_key_prefix(address);
+    key.extend_from_slice(&index.to_be_bytes::<32>());
+    key
+}
+
+pub fn decode_key(key: &[u8]) -> Option<DecodedKey> {
+    let (version, key) = key.split_first()?;
+    if *version != KEY_FORMAT_VERSION || key.len() <= ADDRESS_LEN {
+        return None;
+    }
+    let (address, key) = key.split_at(ADDRESS_LEN);
+    let address = Address::from_slice(address);
+    match key.split_first()? {
+        (category, []) if *category == KeyCategory::AccountInfo as u8 => {
+            Some(DecodedKey::AccountInfo(address))
+        }
+        (category, []) if *category == KeyCategory::AccountState as u8 => {
+            Some(DecodedKey::AccountState(address))
+        }
+        (category, index) if *category == KeyCategory::Storage as u8 && index.len() == 32 => {
+            Some(DecodedKey::Storage(address, U256::from_be_slice(index)))
+        }
+        (category, []) if *category == KeyCategory::StorageUsage as u8 => {
+            Some(DecodedKey::StorageUsage(address))
+        }
+        _ => None,
+    }
+}
+
+/// The key prefixes of the legacy layout, which only supported a fixed set
+/// of addresses: `Address::ZERO`, `Address::ZERO.create(0)` and then the
+/// registered contracts in the order of registration.
+#[repr(u8)]
+enum LegacyKeyTag {
+    /// The tag of `Address::ZERO`.
+    Zero,
+    /// The tag of `Address::ZERO.create(0)`.
+    Created,
+    /// The tag of the first registered contract.
+    FirstRegistered,
+}
+
+fn decode_legacy_key(key: &[u8], registered_addresses: &[Address]) -> Option<DecodedKey> {
+    let (tag, key) = key.split_first()?;
+    let address = if *tag == LegacyKeyTag::Zero as u8 {
+        Address::ZERO
+    } else if *tag == LegacyKeyTag::Created as u8 {
+        Address::ZERO.create(0)
+    } else {
+        let position = tag - LegacyKeyTag::FirstRegistered as u8;
+        *registered_addresses.get(position as usize)?
+    };
+    match key.split_first()? {
+        (category, []) if *category == KeyCategory::AccountInfo as u8 => {
+            Some(DecodedKey::AccountInfo(address))
+        }
+        (category, []) if *category == KeyCategory::AccountState as u8 => {
+            Some(DecodedKey::AccountState(address))
+        }
+        (category, index) if *category == KeyCategory::Storage as u8 => {
+            let index = bcs::from_bytes::<U256>(index).ok()?;
+            Some(DecodedKey::Storage(address, index))
+        }
+        _ => None,
+    }
+}
+
+fn encode_key(key: DecodedKey) -> Vec<u8> {
+    match key {
+        DecodedKey::AccountInfo(address) => account_info_key(&address),
+        DecodedKey::AccountState(address) => account_state_key(&address),
+        DecodedKey::Storage(address, index) => storage_key(&address, index),
+        DecodedKey::StorageUsage(address) => storage_usage_key(&address),
+    }
+}
+
+/// Converts in place the keys of a store from the legacy layout to the
+/// current one, returns the number of converted keys. The values are BCS
+/// encoded in both layouts and are kept as they are.
+///
+/// The keys already in the current layout are left untouched: they have
+/// length 22 or 54 while the legacy keys have length 2, or 35 for the
+/// storage keys `[tag, category, BCS length byte, 32 bytes of the slot]`.
+pub fn migrate_store<C: TestKeyValueStore>(
+    store: &C,
+    registered_addresses: &[Address],
+) -> anyhow::Result<usize>
+where
+    C::Error: Send + Sync + 'static,
+{
+    let entries = read_store_entries(store)?;
+    let mut batch = Batch::new();
+    let mut number_converted = 0;
+    for (key, value) in entries {
+        if decode_key(&key).is_some() {
+            continue;
+        }
+        let decoded_key = decode_legacy_key(&key, registered_addresses).with_context(|| {
+            format!("The key {} is in neither layout", hex::encode(&key))
+        })?;
+        batch.delete_key(key);
+        batch.put_key_value_bytes(encode_key(decoded_key), value);
+        number_converted += 1;
+    }
+    block_on(store.write_batch(batch))?;
+    Ok(number_converted)
+}
+
+/// All the entries of `store`, in the order of the keys.
+pub fn read_store_entries<C: TestKeyValueStore>(store: &C) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>>
+where
+    C::Error: Send + Sync + 'static,
+{
+    let mut entries = Vec::new();
+    for entry in block_on(store.find_key_values_by_prefix(&[]))?.iterator() {
+        let (key, value) = entry?;
+        entries.push((key.to_vec(), value.to_vec()));
+    }
+    Ok(entries)
+}
+
+/// Writes `entries` to `store`, for example to load a raw snapshot.
+pub fn write_store_entries<C: TestKeyValueStore>(
+    store: &C,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+) -> anyhow::Result<()>
+where
+    C::Error: Send + Sync + 'static,
+{
+    let mut batch = Batch::new();
+    for (key, value) in entries {
+        batch.put_key_value_bytes(key, value);
+    }
+    block_on(store.write_batch(batch))?;
+    Ok(())
+}
+
+/// A backend independent snapshot of the content of a store, with the keys
+/// and values in hexadecimal.
+#[derive(Serialize, Deserialize)]
+pub struct RawSnapshot {
+    pub entries: Vec<(String, String)>,
+}
+
+pub fn read_raw_snapshot(path: &Path) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let contents = std::fs::read_to_string(path)?;
+    let snapshot: RawSnapshot = serde_json::from_str(&contents)?;
+    snapshot
+        .entries
+        .into_iter()
+        .map(|(key, value)| Ok((hex::decode(key)?, hex::decode(value)?)))
+        .collect()
+}
+
+pub fn write_raw_snapshot(path: &Path, entries: &[(Vec<u8>, Vec<u8>)]) -> anyhow::Result<()> {
+    let snapshot = RawSnapshot {
+        entries: entries
+            .iter()
+            .map(|(key, value)| (hex::encode(key), hex::encode(value)))
+            .collect(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&snapshot)?)?;
+    Ok(())
+}
@@ -0,0 +1,22 @@
+pub mod access_list;
+pub mod access_set;
+pub mod backend;
+pub mod block;
+pub mod call_failure;
+pub mod differential;
+pub mod dump;
+pub mod estimate;
+pub mod events;
+pub mod genesis;
+pub mod harness;
+pub mod key_format;
+pub mod linera_database;
+pub mod logging;
+pub mod metrics;
+pub mod policy;
+pub mod scenario_file;
+pub mod solidity;
+pub mod spec_diff;
+pub mod state_test;
+pub mod tx_builder;
+pub mod workloads;
@@ -0,0 +1,841 @@
+use std::{
+    collections::{btree_map, BTreeMap, BTreeSet},
+    fmt,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use futures::executor::block_on;
+use linera_views::{
+    batch::{Batch, WriteOperation},
+    store::{KeyValueIterable, TestKeyValueStore},
+};
+use revm_bytecode::Bytecode;
+use revm_database::AccountState;
+use revm_database_interface::{DBErrorMarker, Database, DatabaseCommit, DatabaseRef};
+use revm_primitives::{keccak256, Address, HashMap, B256, U256};
+use revm_state::{Account, AccountInfo};
+use serde::de::DeserializeOwned;
+use tracing::{debug, info, instrument, trace, warn};
+
+use crate::{
+    access_set::{AccessKey, AccessSets},
+    key_format::{
+        account_info_key, account_state_key, decode_key, storage_key, storage_key_prefix,
+        storage_usage_key, DecodedKey,
+    },
+    metrics::StorageMetrics,
+    policy::{QuotaError, StoragePolicy, StorageUsage},
+};
+
+pub(crate) fn from_bytes_option<V: DeserializeOwned>(
+    key_opt: &Option<Vec<u8>>,
+) -> Result<Option<V>, bcs::Error> {
+    match key_opt {
+        Some(bytes) => {
+            let value = bcs::from_bytes(bytes)?;
+            Ok(Some(value))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Key-value pairs as read from the store.
+pub type StoreEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The error of the `LineraDatabase`.
+///
+/// The revm 22+ database traits require the error to implement
+/// `DBErrorMarker`, which cannot be done on the error of the store.
+#[derive(Debug)]
+pub enum LineraDatabaseError<E> {
+    /// An error of the store.
+    Store(E),
+    /// A previous commit could not be written, the content of the store is
+    /// unknown until the database is recreated.
+    FailedCommit(String),
+    /// The code is kept in the account info, so it is never looked up by
+    /// its hash.
+    CodeByHash(B256),
+}
+
+impl<E> From<E> for LineraDatabaseError<E> {
+    fn from(error: E) -> Self {
+        Self::Store(error)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for LineraDatabaseError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(error) => write!(f, "LineraDatabase error: {}", error),
+            Self::FailedCommit(error) => {
+                write!(f, "LineraDatabase error: a previous commit failed: {}", error)
+            }
+            Self::CodeByHash(code_hash) => {
+                write!(f, "LineraDatabase error: no code lookup by hash code_hash={}", code_hash)
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for LineraDatabaseError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Store(error) => Some(error),
+            Self::FailedCommit(_) | Self::CodeByHash(_) => None,
+        }
+    }
+}
+
+impl<E> DBErrorMarker for LineraDatabaseError<E> {}
+
+/// The error of `LineraDatabase::commit_checked`. In both cases nothing
+/// has been written.
+#[derive(Debug)]
+pub enum CommitError<E> {
+    /// The `StoragePolicy` rejected the transaction.
+    Quota(QuotaError),
+    /// The store could not be read or written.
+    Store(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CommitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Quota(error) => write!(f, "The commit was rejected: {}", error),
+            Self::Store(error) => write!(f, "The commit failed: {}", error),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CommitError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Quota(error) => Some(error),
+            Self::Store(error) => Some(error),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StorageStats {
+    pub number_reset: u64,
+    pub number_set: u64,
+    pub number_release: u64,
+    pub number_warm_read: u64,
+    /// The fees charged according to the `StoragePolicy`.
+    pub fees: u64,
+    pub map: BTreeMap<(Address, U256), U256>,
+}
+
+/// The storage operations counted by the `StorageStats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorageCounts {
+    pub number_set: u64,
+    pub number_reset: u64,
+    pub number_release: u64,
+    pub fees: u64,
+}
+
+/// The entries loaded by `LineraDatabase::prefetch`. They are only valid
+/// until the next commit.
+#[derive(Default)]
+struct PrefetchCache {
+    infos: BTreeMap<Address, Option<AccountInfo>>,
+    states: BTreeMap<Address, Option<AccountState>>,
+    usages: BTreeMap<Address, Option<StorageUsage>>,
+    slots: BTreeMap<(Address, U256), U256>,
+}
+
+pub struct LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+{
+    commit_error: Option<C::Error>,
+    quota_error: Option<QuotaError>,
+    policy: StoragePolicy,
+    track_access_sets: bool,
+    read_set: Mutex<BTreeSet<AccessKey>>,
+    access_sets: Vec<AccessSets>,
+    prefetch_cache: Mutex<PrefetchCache>,
+    storage_stats: Arc<Mutex<StorageStats>>,
+    metrics: Arc<StorageMetrics>,
+    registered_addresses: Vec<Address>,
+    db: C,
+}
+
+impl<C> Database for LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: 'static,
+{
+    type Error = LineraDatabaseError<C::Error>;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.basic_ref(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.storage_ref(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.throw_error()?;
+        <Self as DatabaseRef>::block_hash_ref(self, number)
+    }
+}
+
+impl<C> DatabaseCommit for LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: 'static,
+{
+    /// A commit rejected by the `StoragePolicy` is kept for
+    /// `take_quota_error`, a failure of the store makes the following reads
+    /// fail. The `ContractHarness` uses `commit_checked` instead, which
+    /// returns both.
+    fn commit(&mut self, changes: HashMap<Address, Account>) {
+        match self.commit_checked(changes) {
+            Ok(()) => {}
+            Err(CommitError::Quota(error)) => self.quota_error = Some(error),
+            Err(CommitError::Store(error)) => self.commit_error = Some(error),
+        }
+    }
+}
+
+impl<C> LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: 'static,
+{
+    #[instrument(name = "commit", skip_all, fields(accounts = changes.len()))]
+    fn commit_with_error(
+        &mut self,
+        changes: HashMap<Address, Account>,
+    ) -> Result<Result<(), QuotaError>, C::Error> {
+        let mut batch = Batch::new();
+        let mut list_new_balances = Vec::new();
+        let mut increment_number_reset = 0;
+        let mut increment_number_set = 0;
+        let mut increment_number_release = 0;
+        let mut cleared_addresses = Vec::new();
+        let mut written_slots = Vec::new();
+        // The metrics are only recorded once the batch is accepted.
+        let mut operations = Vec::new();
+        let mut bytes_written_by_address = Vec::new();
+        let mut fees = Vec::new();
+        for (address, account) in changes {
+            if !account.is_touched() {
+                continue;
+            }
+            if self.is_known_address(&address) {
+                let operations_start = batch.operations.len();
+                let key_prefix = storage_key_prefix(&address);
+                let key_info = account_info_key(&address);
+                let key_state = account_state_key(&address);
+                let key_usage = storage_usage_key(&address);
+                // Since EIP-6780 (Cancun), revm only marks an account as
+                // selfdestructed if it was created in the same transaction.
+                // Otherwise the SELFDESTRUCT only moves the balance to the
+                // beneficiary, which shows up as a plain balance change.
+                if account.is_selfdestructed() {
+                    debug!(%address, "selfdestruct");
+                    batch.delete_key_prefix(key_prefix);
+                    batch.delete_key(key_info);
+                    batch.delete_key(key_usage);
+                    batch.put_key_value(key_state, &AccountState::NotExisting)?;
+                    cleared_addresses.push(address);
+                } else {
+                    let is_newly_created = account.is_created();
+                    batch.put_key_value(key_info, &account.info)?;
+
+                    let (account_state, mut usage) = if is_newly_created {
+                        batch.delete_key_prefix(key_prefix);
+                        cleared_addresses.push(address);
+                        (AccountState::StorageCleared, StorageUsage::default())
+                    } else {
+                        let account_state = self.read_account_state(&address)?.unwrap_or_default();
+                        let account_state = if account_state.is_storage_cleared() {
+                            AccountState::StorageCleared
+                        } else {
+                            AccountState::Touched
+                        };
+                        let usage = self.read_storage_usage(&address)?.unwrap_or_default();
+                        (account_state, usage)
+                    };
+                    batch.put_key_value(key_state, &account_state)?;
+                    let mut number_set = 0;
+                    let mut number_reset = 0;
+                    let mut number_release = 0;
+                    let mut bytes_added = 0;
+                    let mut bytes_removed = 0;
+                    for (index, value) in account.storage {
+                        written_slots.push((address, index, value.present_value()));
+                        let key = storage_key(&address, index);
+                        let present_len = bcs::serialized_size(&value.present_value())? as u64;
+                        let original_len = bcs::serialized_size(&value.original_value())? as u64;
+                        if value.original_value() == U256::ZERO {
+                            if value.present_value() != U256::ZERO {
+                                debug!(%address, slot = %index, value = %value.present_value(), case = "A", "storage write");
+                                bytes_added += key.len() as u64 + present_len;
+                                batch.put_key_value(key, &value.present_value())?;
+                                number_set += 1;
+                                operations.push((address, "set"));
+                            } else {
+                                debug!(%address, slot = %index, value = %value.present_value(), case = "B", "storage write");
+                            }
+                        } else {
+                            if value.present_value() != U256::ZERO {
+                                if value.present_value() == value.original_value() {
+                                    debug!(%address, slot = %index, value = %value.present_value(), case = "C", "storage write");
+                                } else {
+                                    debug!(%address, slot = %index, value = %value.present_value(), case = "D", "storage write");
+                                    bytes_added += present_len;
+                                    bytes_removed += original_len;
+                                    batch.put_key_value(key, &value.present_value())?;
+                                    number_reset += 1;
+                                    operations.push((address, "reset"));
+                                }
+                            } else {
+                                debug!(%address, slot = %index, value = %value.present_value(), case = "E", "storage write");
+                                bytes_removed += key.len() as u64 + original_len;
+                                batch.delete_key(key);
+                                number_release += 1;
+                                operations.push((address, "release"));
+                            }
+                        }
+                    }
+                    usage.slots = (usage.slots + number_set).saturating_sub(number_release);
+                    usage.bytes = (usage.bytes + bytes_added).saturating_sub(bytes_removed);
+                    if let Err(error) = self.policy.check(address, &usage) {
+                        // Nothing has been written: dropping the batch rolls
+                        // the whole transaction back, including the nonce
+                        // and balance changes of the other accounts.
+                        warn!(%error, "storage quota exceeded, the transaction is rolled back");
+                        return Ok(Err(error));
+                    }
+                    batch.put_key_value(key_usage, &usage)?;
+                    let fee = self.policy.fee(number_set, number_reset, number_release);
+                    fees.push((address, fee));
+                    increment_number_set += number_set;
+                    increment_number_reset += number_reset;
+                    increment_number_release += number_release;
+                }
+                let bytes_written = batch.operations[operations_start..]
+                    .iter()
+                    .map(|operation| match operation {
+                        WriteOperation::Put { key, value } => key.len() + value.len(),
+                        _ => 0,
+                    })
+                    .sum();
+                bytes_written_by_address.push((address, bytes_written));
+            } else {
+                if !account.storage.is_empty() {
+                    panic!("For user account, storage must be empty");
+                }
+                // The only allowed operations are the ones for the
+                // account balances.
+                let new_balance = (address, account.info.balance);
+                list_new_balances.push(new_balance);
+            }
+        }
+        self.metrics.record_batch_size(batch.operations.len());
+        self.write_batch(batch)?;
+        if !list_new_balances.is_empty() {
+            panic!("The conversion Ethereum address / Linera address is not yet implemented");
+        }
+        for (address, operation) in operations {
+            self.metrics.record_operation(&address, operation);
+        }
+        for (address, bytes_written) in bytes_written_by_address {
+            self.metrics.record_bytes_written(&address, bytes_written);
+        }
+        let mut increment_fees: u64 = 0;
+        for (address, fee) in fees {
+            self.metrics.record_fee(&address, fee);
+            increment_fees = increment_fees.saturating_add(fee);
+        }
+        debug!(
+            reset = increment_number_reset,
+            set = increment_number_set,
+            release = increment_number_release,
+            fees = increment_fees,
+            "commit done"
+        );
+        let mut storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+        // The cached reads have to follow the committed state, otherwise a
+        // destroyed or re-created contract would see its former slots.
+        for ((address, _), value) in storage_stats.map.iter_mut() {
+            if cleared_addresses.contains(address) {
+                *value = U256::ZERO;
+            }
+        }
+        for (address, index, value) in written_slots {
+            if let Some(entry) = storage_stats.map.get_mut(&(address, index)) {
+                *entry = value;
+            }
+        }
+        storage_stats.number_reset += increment_number_reset;
+        storage_stats.number_set += increment_number_set;
+        storage_stats.number_release += increment_number_release;
+        storage_stats.fees = storage_stats.fees.saturating_add(increment_fees);
+        Ok(Ok(()))
+    }
+
+    #[instrument(name = "basic", skip_all, fields(%address))]
+    fn read_account_info(&self, address: Address) -> Result<Option<AccountInfo>, C::Error> {
+        if self.is_known_address(&address) {
+            let prefetch_cache = self.prefetch_cache.lock().expect("The lock should be possible");
+            if let Some(account_info) = prefetch_cache.infos.get(&address) {
+                debug!(prefetched = true, "account info read");
+                return Ok(account_info.clone());
+            }
+            drop(prefetch_cache);
+            let key = account_info_key(&address);
+            let result = self.read_value_bytes(&key)?;
+            let account_info = from_bytes_option::<AccountInfo>(&result)?;
+            return Ok(account_info);
+        }
+        panic!("only contract address are supported thus far address={address:?}");
+    }
+
+    #[instrument(name = "storage", skip_all, fields(%address, slot = %index))]
+    fn read_storage(&self, address: Address, index: U256) -> Result<U256, C::Error> {
+        if !self.is_known_address(&address) {
+            panic!("There is no storage associated to Externally Owned Account");
+        }
+        let mut storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+        match storage_stats.map.entry((address, index)) {
+            btree_map::Entry::Occupied(entry) => {
+                let result = *entry.get();
+                storage_stats.number_warm_read += 1;
+                self.metrics.record_operation(&address, "warm_read");
+                debug!(value = %result, hot = true, "storage read");
+                Ok(result)
+            },
+            btree_map::Entry::Vacant(entry) => {
+                let prefetch_cache = self.prefetch_cache.lock().expect("The lock should be possible");
+                if let Some(result) = prefetch_cache.slots.get(&(address, index)) {
+                    debug!(value = %result, prefetched = true, "storage read");
+                    return Ok(*result);
+                }
+                drop(prefetch_cache);
+                let key = storage_key(&address, index);
+                let result = self.read_value_bytes(&key)?;
+                let result = from_bytes_option::<U256>(&result)?.unwrap_or_default();
+                self.metrics.record_operation(&address, "cold_read");
+                debug!(value = %result, hot = false, "storage read");
+                entry.insert(result);
+                Ok(result)
+            },
+        }
+    }
+}
+
+impl<C> LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: 'static,
+{
+    fn read_account_state(&self, address: &Address) -> Result<Option<AccountState>, C::Error> {
+        let prefetch_cache = self.prefetch_cache.lock().expect("The lock should be possible");
+        if let Some(account_state) = prefetch_cache.states.get(address) {
+            return Ok(account_state.clone());
+        }
+        drop(prefetch_cache);
+        let result = self.read_value_bytes(&account_state_key(address))?;
+        Ok(from_bytes_option::<AccountState>(&result)?)
+    }
+
+    fn read_storage_usage(&self, address: &Address) -> Result<Option<StorageUsage>, C::Error> {
+        let prefetch_cache = self.prefetch_cache.lock().expect("The lock should be possible");
+        if let Some(usage) = prefetch_cache.usages.get(address) {
+            return Ok(*usage);
+        }
+        drop(prefetch_cache);
+        let result = self.read_value_bytes(&storage_usage_key(address))?;
+        Ok(from_bytes_option::<StorageUsage>(&result)?)
+    }
+
+    /// Loads with a single multi-key read the entries that a transaction is
+    /// expected to access: for every contract of `access_list`, the account
+    /// info (which carries the code), the account state, the storage usage
+    /// and the listed slots. The Externally Owned Accounts are skipped.
+    ///
+    /// The entries are used until the next commit. The slots read from there
+    /// are counted as prefetched, not as cold or warm reads of `StorageStats`.
+    #[instrument(name = "prefetch", skip_all, fields(accounts = access_list.len()))]
+    pub fn prefetch(&self, access_list: &[(Address, Vec<U256>)]) -> Result<(), C::Error> {
+        let mut keys = Vec::new();
+        {
+            let storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+            let prefetch_cache = self.prefetch_cache.lock().expect("The lock should be possible");
+            for (address, indices) in access_list {
+                if !self.is_known_address(address) {
+                    continue;
+                }
+                keys.push(account_info_key(address));
+                keys.push(account_state_key(address));
+                keys.push(storage_usage_key(address));
+                for index in indices {
+                    let slot = (*address, *index);
+                    if !storage_stats.map.contains_key(&slot) && !prefetch_cache.slots.contains_key(&slot) {
+                        keys.push(storage_key(address, *index));
+                    }
+                }
+            }
+        }
+        let values = self.read_multi_values_bytes(keys.clone())?;
+        debug!(keys = keys.len(), "prefetch done");
+        let mut prefetch_cache = self.prefetch_cache.lock().expect("The lock should be possible");
+        for (key, value) in keys.iter().zip(values) {
+            match decode_key(key).expect("The prefetched keys are valid") {
+                DecodedKey::AccountInfo(address) => {
+                    let account_info = from_bytes_option::<AccountInfo>(&value)?;
+                    prefetch_cache.infos.insert(address, account_info);
+                }
+                DecodedKey::AccountState(address) => {
+                    let account_state = from_bytes_option::<AccountState>(&value)?;
+                    prefetch_cache.states.insert(address, account_state);
+                }
+                DecodedKey::StorageUsage(address) => {
+                    let usage = from_bytes_option::<StorageUsage>(&value)?;
+                    prefetch_cache.usages.insert(address, usage);
+                }
+                DecodedKey::Storage(address, index) => {
+                    let value = from_bytes_option::<U256>(&value)?.unwrap_or_default();
+                    self.metrics.record_operation(&address, "prefetch");
+                    prefetch_cache.slots.insert((address, index), value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn clear_prefetch_cache(&self) {
+        let mut prefetch_cache = self.prefetch_cache.lock().expect("The lock should be possible");
+        *prefetch_cache = PrefetchCache::default();
+    }
+}
+
+impl<C> DatabaseRef for LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: 'static,
+{
+    type Error = LineraDatabaseError<C::Error>;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.throw_error()?;
+        self.record_read(AccessKey::AccountInfo(address));
+        Ok(self.read_account_info(address)?)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        Err(LineraDatabaseError::CodeByHash(code_hash))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.throw_error()?;
+        self.record_read(AccessKey::Storage(address, index));
+        Ok(self.read_storage(address, index)?)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.throw_error()?;
+        Ok(keccak256(number.to_string().as_bytes()))
+    }
+}
+
+impl<C> LineraDatabase<C>
+where
+    C: TestKeyValueStore,
+    C::Error: 'static,
+{
+    pub(crate) fn read_value_bytes(&self, key: &[u8]) -> Result<Option<Vec<u8>>, C::Error> {
+        let start = Instant::now();
+        let result = block_on(self.db.read_value_bytes(key));
+        self.metrics.record_store_latency("read_value_bytes", start.elapsed());
+        result
+    }
+
+    fn read_multi_values_bytes(&self, keys: Vec<Vec<u8>>) -> Result<Vec<Option<Vec<u8>>>, C::Error> {
+        let start = Instant::now();
+        let result = block_on(self.db.read_multi_values_bytes(keys));
+        self.metrics.record_store_latency("read_multi_values_bytes", start.elapsed());
+        result
+    }
+
+    pub(crate) fn find_key_values_by_prefix(
+        &self,
+        key_prefix: &[u8],
+    ) -> Result<StoreEntries, C::Error> {
+        let start = Instant::now();
+        let key_values = block_on(self.db.find_key_values_by_prefix(key_prefix))?;
+        let result = key_values.into_iterator_owned().collect::<Result<_, _>>()?;
+        self.metrics.record_store_latency("find_key_values_by_prefix", start.elapsed());
+        Ok(result)
+    }
+
+    fn write_batch(&self, batch: Batch) -> Result<(), C::Error> {
+        let start = Instant::now();
+        let result = block_on(self.db.write_batch(batch));
+        self.metrics.record_store_latency("write_batch", start.elapsed());
+        result
+    }
+
+    /// The accounts handled by the database. The other addresses are the
+    /// Externally Owned Accounts whose balances live on the Linera side.
+    pub(crate) fn is_known_address(&self, address: &Address) -> bool {
+        trace!(%address, "is_known_address");
+        address == &Address::ZERO
+            || address == &Address::ZERO.create(0)
+            || self.registered_addresses.contains(address)
+    }
+
+    pub fn registered_addresses(&self) -> &[Address] {
+        &self.registered_addresses
+    }
+
+    /// Contracts created by other contracts (for example with CREATE2) need
+    /// to be registered before the transaction creating them is run.
+    pub fn register_contract_address(&mut self, address: Address) {
+        if !self.is_known_address(&address) {
+            self.registered_addresses.push(address);
+        }
+    }
+
+    /// Reads `count` adjacent slots starting at `start`, as used by arrays
+    /// and structs, with a single prefix scan. The big-endian encoding of the
+    /// slots makes the range a contiguous run of keys under their common
+    /// prefix. The range stops at the slot `U256::MAX`, fewer than `count`
+    /// values are then returned.
+    ///
+    /// The values are the committed ones, read from the store without going
+    /// through the cache of `StorageStats`, which follows every commit, and
+    /// without being counted as reads.
+    pub fn read_storage_range(
+        &self,
+        address: Address,
+        start: U256,
+        count: usize,
+    ) -> Result<Vec<U256>, C::Error> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let end = start.saturating_add(U256::from(count - 1));
+        let start_key = storage_key(&address, start);
+        let end_key = storage_key(&address, end);
+        let common_len = start_key
+            .iter()
+            .zip(&end_key)
+            .take_while(|(x, y)| x == y)
+            .count();
+        let key_prefix = &start_key[..common_len];
+        let mut values = vec![U256::ZERO; (end - start).to::<usize>() + 1];
+        for (key, value) in self.find_key_values_by_prefix(key_prefix)? {
+            let full_key = [key_prefix, &key].concat();
+            let Some(DecodedKey::Storage(_, index)) = decode_key(&full_key) else {
+                continue;
+            };
+            if index < start || index > end {
+                continue;
+            }
+            values[(index - start).to::<usize>()] = bcs::from_bytes(&value)?;
+        }
+        Ok(values)
+    }
+
+    pub fn insert_account_info(&mut self, address: Address, info: AccountInfo) -> Result<(), C::Error> {
+        if !self.is_known_address(&address) {
+            panic!("Only contract addresses can hold an account info address={address:?}");
+        }
+        let mut batch = Batch::new();
+        batch.put_key_value(account_info_key(&address), &info)?;
+        self.write_batch(batch)?;
+        // The prefetched account info would hide the new one.
+        self.clear_prefetch_cache();
+        Ok(())
+    }
+
+    /// Turns on the recording of the read sets and of the access sets of
+    /// the commits. It is off by default since `write_set` costs one more
+    /// read of the account info per touched account and commit.
+    pub fn set_track_access_sets(&mut self, track_access_sets: bool) {
+        self.track_access_sets = track_access_sets;
+    }
+
+    fn record_read(&self, key: AccessKey) {
+        if !self.track_access_sets {
+            return;
+        }
+        let mut read_set = self.read_set.lock().expect("The lock should be possible");
+        read_set.insert(key);
+    }
+
+    /// Returns the entries read since the last commit or the last call,
+    /// when `set_track_access_sets` is on.
+    /// A transaction executed speculatively, without commit, gets its reads
+    /// from there and its writes from `write_set`.
+    pub fn take_read_set(&self) -> BTreeSet<AccessKey> {
+        let mut read_set = self.read_set.lock().expect("The lock should be possible");
+        std::mem::take(&mut *read_set)
+    }
+
+    /// The entries that committing `changes` modifies. revm marks every
+    /// loaded account as touched, so the account infos are compared with
+    /// the ones in the store to keep only the real writes.
+    pub fn write_set(
+        &self,
+        changes: &HashMap<Address, Account>,
+    ) -> Result<BTreeSet<AccessKey>, C::Error> {
+        let mut writes = BTreeSet::new();
+        for (address, account) in changes {
+            if !account.is_touched() {
+                continue;
+            }
+            if !self.is_known_address(address) {
+                writes.insert(AccessKey::AccountInfo(*address));
+                continue;
+            }
+            if account.is_selfdestructed() || account.is_created() {
+                writes.insert(AccessKey::AccountInfo(*address));
+                writes.insert(AccessKey::AllStorage(*address));
+            } else if self.read_account_info(*address)?.as_ref() != Some(&account.info) {
+                writes.insert(AccessKey::AccountInfo(*address));
+            }
+            if account.is_selfdestructed() {
+                continue;
+            }
+            for (index, value) in &account.storage {
+                if value.present_value() != value.original_value() {
+                    writes.insert(AccessKey::Storage(*address, *index));
+                }
+            }
+        }
+        Ok(writes)
+    }
+
+    /// Returns the access sets of the transactions committed since the last
+    /// call, in the order of commit, when `set_track_access_sets` is on.
+    pub fn take_access_sets(&mut self) -> Vec<AccessSets> {
+        std::mem::take(&mut self.access_sets)
+    }
+
+    /// Fails every read once a commit could not be written.
+    fn throw_error(&self) -> Result<(), LineraDatabaseError<C::Error>> {
+        if let Some(error) = &self.commit_error {
+            return Err(LineraDatabaseError::FailedCommit(error.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn new(db: C) -> Self {
+        Self::with_metrics(db, Arc::new(StorageMetrics::new()))
+    }
+
+    /// Creates a database reporting to an existing metrics registry, so that
+    /// several databases can be exported on the same endpoint.
+    pub fn with_metrics(db: C, metrics: Arc<StorageMetrics>) -> Self {
+        let storage_stats = StorageStats::default();
+        Self {
+            commit_error: None,
+            quota_error: None,
+            policy: StoragePolicy::default(),
+            track_access_sets: false,
+            read_set: Mutex::new(BTreeSet::new()),
+            access_sets: Vec::new(),
+            prefetch_cache: Mutex::new(PrefetchCache::default()),
+            storage_stats: Arc::new(Mutex::new(storage_stats)),
+            metrics,
+            registered_addresses: Vec::new(),
+            db,
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: StoragePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> &StoragePolicy {
+        &self.policy
+    }
+
+    /// Commits `changes`, unless the `StoragePolicy` rejects them: nothing
+    /// is then written, no access sets are recorded and the `QuotaError` is
+    /// returned. The errors of the store are returned as well.
+    pub fn commit_checked(
+        &mut self,
+        changes: HashMap<Address, Account>,
+    ) -> Result<(), CommitError<C::Error>> {
+        let access_sets = if self.track_access_sets {
+            let writes = self.write_set(&changes).map_err(CommitError::Store)?;
+            let reads = self.take_read_set();
+            Some(AccessSets { reads, writes })
+        } else {
+            None
+        };
+        let result = self.commit_with_error(changes);
+        self.clear_prefetch_cache();
+        match result {
+            Ok(Ok(())) => {
+                self.access_sets.extend(access_sets);
+                Ok(())
+            }
+            Ok(Err(error)) => Err(CommitError::Quota(error)),
+            Err(error) => Err(CommitError::Store(error)),
+        }
+    }
+
+    /// Returns the error of the last transaction rejected by the
+    /// `StoragePolicy` in a plain `commit`. Such a transaction has been
+    /// rolled back: the revm execution result reports it as executed but
+    /// none of its changes are in the store.
+    pub fn take_quota_error(&mut self) -> Option<QuotaError> {
+        self.quota_error.take()
+    }
+
+    /// The fees charged since the last reset of the storage stats.
+    pub fn charged_fees(&self) -> u64 {
+        let storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+        storage_stats.fees
+    }
+
+    /// The storage operations since the last reset of the storage stats.
+    pub fn storage_counts(&self) -> StorageCounts {
+        let storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+        StorageCounts {
+            number_set: storage_stats.number_set,
+            number_reset: storage_stats.number_reset,
+            number_release: storage_stats.number_release,
+            fees: storage_stats.fees,
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<StorageMetrics> {
+        self.metrics.clone()
+    }
+
+    pub fn reset_storage_stats(&self) {
+        let mut storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+        *storage_stats = StorageStats::default();
+    }
+
+    pub fn print_status(&self) {
+        let storage_stats = self.storage_stats.lock().expect("The lock should be possible");
+        info!(
+            number_reset = storage_stats.number_reset,
+            number_set = storage_stats.number_set,
+            number_release = storage_stats.number_release,
+            number_warm_read = storage_stats.number_warm_read,
+            number_cold_read = storage_stats.map.len(),
+            fees = storage_stats.fees,
+            "storage stats"
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// Installs the global subscriber.
+///
+/// The filter is read from `RUST_LOG` (for example
+/// `RUST_LOG=test_linera_database::linera_database=debug`) and defaults to
+/// `info`. Setting `LOG_FORMAT=json` emits one JSON object per event, with
+/// the fields of the enclosing spans.
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let is_json = std::env::var("LOG_FORMAT").is_ok_and(|format| format == "json");
+    let builder = fmt().with_env_filter(filter).with_target(true);
+    if is_json {
+        builder.json().with_current_span(true).with_span_list(true).init();
+    } else {
+        builder.init();
+    }
+}
@@ -0,0 +1,1317 @@
+use alloy_dyn_abi::DynSolValue;
+use alloy_sol_types::{sol, SolCall};
+use anyhow::Context;
+use futures::executor::block_on;
+use linera_views::{memory::MemoryStore, store::TestKeyValueStore};
+use revm::primitives::Bytes;
+use revm_context::{result::InvalidTransaction, BlockEnv, TxEnv};
+use revm_database::{AccountState, DatabaseRef, InMemoryDB};
+use revm_primitives::{
+    alloy_primitives::BloomInput, hardfork::SpecId, keccak256, Address, TxKind, B256, U256,
+};
+use revm_state::AccountInfo;
+use std::{path::Path, sync::Arc};
+
+use test_linera_database::{
+    access_set::{find_conflicts, AccessKey, AccessSets},
+    backend::Backend,
+    block::BlockOutcome,
+    call_failure::{CallFailure, RevertReason},
+    differential::{AccountSummary, DifferentialRunner, Divergence},
+    genesis::{export_genesis_alloc, import_genesis_alloc, load_genesis_alloc, save_genesis_alloc},
+    harness::ContractHarness,
+    key_format::{decode_key, migrate_store, read_store_entries, write_store_entries, DecodedKey, KeyCategory},
+    linera_database::LineraDatabase,
+    logging::init_tracing,
+    metrics::{serve_metrics, StorageMetrics},
+    policy::{QuotaError, StoragePolicy},
+    solidity::{get_bytecode, get_bytecode_and_abi},
+    spec_diff::{run_across_specs, Scenario, ScenarioCall},
+    state_test::{fork_spec_id, load_state_tests, run_state_test},
+    workloads::{balance_of_args, erc20_bytecode, erc20_bytecode_and_abi, key_value_map_bytecode, transfer_args, Operation, TOKEN_SUPPLY},
+};
+use tracing::{debug, info, info_span};
+
+fn single_execution_operation<DB: Backend>(
+    harness: &mut ContractHarness<DB>,
+    operation: Operation,
+) -> anyhow::Result<()> {
+    let span = info_span!("operation", ?operation);
+    let _guard = span.enter();
+    let contract_address = Address::ZERO.create(0);
+    harness.call(contract_address, operation.abi_encode())?;
+    Ok(())
+}
+
+/// The Externally Owned Accounts are not supported by the `LineraDatabase`,
+/// so the users of a scenario are registered as accounts of the database,
+/// see `Backend::add_account`.
+fn add_user_accounts<DB: Backend>(db: &mut DB, accounts: &[(Address, AccountInfo)]) -> anyhow::Result<()> {
+    for (address, info) in accounts {
+        db.add_account(*address, Some(info.clone()))?;
+    }
+    Ok(())
+}
+
+fn key_value_map_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("key_value_map_scenario", ?spec_id);
+    let _guard = span.enter();
+    let tx_data = key_value_map_bytecode()?;
+
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let contract_address = harness.deploy(tx_data)?;
+    assert_eq!(contract_address, Address::ZERO.create(0));
+
+
+    for operation in [Operation::DeleteKey(U256::from(7)),
+                      Operation::InsertKeyValue(U256::from(7), U256::from(5)),
+                      Operation::InsertKeyValue(U256::from(7), U256::from(5)),
+                      Operation::InsertKeyValue(U256::from(7), U256::from(7)),
+                      Operation::InsertKeyValueBis(U256::from(7), U256::from(5)),
+                      Operation::ReadValue(U256::from(7)),
+                      Operation::DeleteKey(U256::from(7)),
+                      Operation::ReadValue(U256::from(7)),
+                      Operation::ReadValue(U256::from(5))] {
+        single_execution_operation(&mut harness, operation)?;
+        harness.db().print_status();
+        harness.db().reset_storage_stats();
+    }
+    Ok(())
+}
+
+/// The slot of `map[key]` for the mapping at slot 0 of ExampleKeyValueMap.
+fn mapping_slot(key: U256) -> U256 {
+    let preimage = [key.to_be_bytes::<32>(), U256::ZERO.to_be_bytes::<32>()].concat();
+    U256::from_be_bytes(keccak256(preimage).0)
+}
+
+/// Runs the same operations with and without prefetching the access list
+/// of every transaction, and checks that the resulting states are equal
+/// and that the prefetching saves store calls.
+fn prefetch_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("prefetch_scenario", ?spec_id);
+    let _guard = span.enter();
+    let tx_data = key_value_map_bytecode()?;
+
+    let mut dumps = Vec::new();
+    let mut store_calls = Vec::new();
+    for use_prefetch in [false, true] {
+        let store_calls_start = metrics.store_calls();
+        let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+        let contract_address = harness.deploy(tx_data.clone())?;
+        for operation in [Operation::InsertKeyValue(U256::from(7), U256::from(5)),
+                          Operation::InsertKeyValueBis(U256::from(7), U256::from(5)),
+                          Operation::InsertKeyValue(U256::from(8), U256::from(1)),
+                          Operation::DeleteKey(U256::from(7)),
+                          Operation::ReadValue(U256::from(8))] {
+            if use_prefetch {
+                let access_list = [(Address::ZERO, Vec::new()), (contract_address, vec![mapping_slot(operation.key())])];
+                harness.db().prefetch(&access_list)?;
+            }
+            single_execution_operation(&mut harness, operation)?;
+        }
+        store_calls.push(metrics.store_calls() - store_calls_start);
+        harness.db().print_status();
+        dumps.push(harness.db().dump_all()?.to_json()?);
+    }
+    assert_eq!(dumps[0], dumps[1]);
+    // A single multi-key read replaces the reads of the account infos,
+    // states, usages and slots of every transaction.
+    assert!(store_calls[1] < store_calls[0], "store calls {store_calls:?}");
+    Ok(())
+}
+
+/// Checks that a transaction exceeding the storage quota of the contract is
+/// rolled back and that the fees follow the `StoragePolicy`.
+fn quota_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("quota_scenario", ?spec_id);
+    let _guard = span.enter();
+    let tx_data = key_value_map_bytecode()?;
+
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let policy = StoragePolicy {
+        max_slots_per_contract: Some(2),
+        max_bytes_per_contract: None,
+        fee_per_set: 20000,
+        fee_per_reset: 5000,
+        fee_per_release: 0,
+    };
+    harness.db_mut().set_policy(policy);
+    harness.db_mut().set_track_access_sets(true);
+    let contract_address = harness.deploy(tx_data)?;
+
+    for key in [1, 2] {
+        let operation = Operation::InsertKeyValue(U256::from(key), U256::from(key));
+        single_execution_operation(&mut harness, operation)?;
+    }
+    assert_eq!(harness.db().charged_fees(), 40000);
+
+    // The third slot exceeds the quota: the call fails with the
+    // `QuotaError` and the transaction is rolled back, nonce included.
+    let nonce = harness.nonce(Address::ZERO)?;
+    harness.db_mut().take_access_sets();
+    let operation = Operation::InsertKeyValue(U256::from(3), U256::from(3));
+    let error = single_execution_operation(&mut harness, operation).expect_err("The quota should be exceeded");
+    let expected_error = QuotaError::TooManySlots {
+        address: contract_address,
+        slots: 3,
+        max_slots: 2,
+    };
+    assert_eq!(error.downcast_ref::<QuotaError>(), Some(&expected_error));
+    assert_eq!(harness.nonce(Address::ZERO)?, nonce);
+    assert_eq!(harness.next_nonce(Address::ZERO)?, nonce);
+    assert!(harness.db_mut().take_access_sets().is_empty());
+    assert_eq!(harness.db().dump_account(contract_address)?.storage.len(), 2);
+    assert_eq!(harness.db().charged_fees(), 40000);
+
+    // Releasing a slot makes room for the third one.
+    single_execution_operation(&mut harness, Operation::DeleteKey(U256::from(1)))?;
+    single_execution_operation(&mut harness, Operation::InsertKeyValue(U256::from(3), U256::from(3)))?;
+    single_execution_operation(&mut harness, Operation::InsertKeyValue(U256::from(3), U256::from(4)))?;
+    let db = harness.db_mut();
+    assert_eq!(db.charged_fees(), 65000);
+    let usage = db.dump_account(contract_address)?.usage.context("The usage should be stored")?;
+    assert_eq!(usage.slots, 2);
+    db.print_status();
+    Ok(())
+}
+
+/// Runs three transactions of different callers speculatively against the
+/// same base state and checks that only the one reading a slot written by
+/// an earlier transaction conflicts.
+fn speculative_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("speculative_scenario", ?spec_id);
+    let _guard = span.enter();
+    sol! {
+        function insert_key_value(uint256 key, uint256 value);
+        function read_value(uint256 key) returns (uint256);
+    }
+    let tx_data = key_value_map_bytecode()?;
+
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let contract_address = harness.deploy(tx_data)?;
+    let callers = [0xaa, 0xbb, 0xcc].map(Address::repeat_byte);
+    add_user_accounts(harness.db_mut(), &callers.map(|caller| (caller, AccountInfo::default())))?;
+    harness.db_mut().set_track_access_sets(true);
+
+    let transactions: [Bytes; 3] = [
+        insert_key_valueCall { key: U256::from(1), value: U256::from(10) }.abi_encode().into(),
+        insert_key_valueCall { key: U256::from(2), value: U256::from(20) }.abi_encode().into(),
+        read_valueCall { key: U256::from(1) }.abi_encode().into(),
+    ];
+    let mut access_sets = Vec::new();
+    let mut states = Vec::new();
+    for (caller, encoded_args) in callers.into_iter().zip(transactions.clone()) {
+        let kind = TxKind::Call(contract_address);
+        let state = harness.transact(caller, kind, encoded_args)?.state;
+        let reads = harness.db().take_read_set();
+        let writes = harness.db().write_set(&state)?;
+        access_sets.push(AccessSets { reads, writes });
+        states.push(state);
+    }
+    let conflicts = find_conflicts(&access_sets);
+    info!(?conflicts, "speculative execution validated");
+    assert_eq!(conflicts.len(), 1);
+    let conflict = conflicts[0];
+    assert_eq!((conflict.transaction, conflict.writer), (2, 0));
+    assert!(matches!(conflict.key, AccessKey::Storage(address, _) if address == contract_address));
+
+    // The valid transactions are committed in order, the conflicting one is
+    // re-executed on top of them.
+    for state in states.into_iter().take(2) {
+        harness.commit(state)?;
+    }
+    let kind = TxKind::Call(contract_address);
+    let result_and_state = harness.transact(callers[2], kind, transactions[2].clone())?;
+    let output = harness.call_output(contract_address, result_and_state.result)?;
+    assert_eq!(read_valueCall::abi_decode_returns(&output)?, U256::from(10));
+    harness.commit(result_and_state.state)?;
+    let committed_sets = harness.db_mut().take_access_sets();
+    assert_eq!(committed_sets.len(), 3);
+    assert_eq!(committed_sets[2].reads, access_sets[2].reads);
+    harness.db().print_status();
+    Ok(())
+}
+
+/// Checks the decoding of the failed calls: revert strings, panics, custom
+/// errors, empty reverts and halts.
+fn failure_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("failure_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+contract Failures {
+  error InsufficientBalance(uint256 available, uint256 required);
+
+  uint256 value;
+
+  function fail_require(uint256 x) external {
+    require(x > 10, "x is too small");
+    value = x;
+  }
+
+  function fail_panic(uint256 x) external returns (uint256) {
+    return 10 / x;
+  }
+
+  function fail_custom(uint256 required) external {
+    revert InsufficientBalance(value, required);
+  }
+
+  function fail_empty() external {
+    revert();
+  }
+
+  function fail_invalid() external {
+    assembly { invalid() }
+  }
+}
+"#
+    .to_string();
+    let (bytecode, abi) = get_bytecode_and_abi(&source_code, "Failures")?;
+
+    sol! {
+        function fail_require(uint256 x);
+        function fail_panic(uint256 x) returns (uint256);
+        function fail_custom(uint256 required);
+        function fail_empty();
+        function fail_invalid();
+    }
+
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let contract_address = harness.deploy_with_abi(bytecode, abi)?;
+
+    harness.call_typed(contract_address, &fail_requireCall { x: U256::from(12) })?;
+    let failures = [
+        harness.call_typed(contract_address, &fail_requireCall { x: U256::from(3) }).map(|_| ()),
+        harness.call_typed(contract_address, &fail_panicCall { x: U256::ZERO }).map(|_| ()),
+        harness.call_typed(contract_address, &fail_customCall { required: U256::from(20) }).map(|_| ()),
+        harness.call_typed(contract_address, &fail_emptyCall).map(|_| ()),
+        harness.call_typed(contract_address, &fail_invalidCall).map(|_| ()),
+    ];
+    let mut failures = failures.into_iter().map(|result| {
+        let error = result.expect_err("The call should fail");
+        info!(%error, "call failed");
+        error.downcast::<CallFailure>().expect("The error should be a CallFailure")
+    });
+
+    let failure = failures.next().unwrap();
+    assert_eq!(failure.revert_reason(), Some(&RevertReason::Error("x is too small".to_string())));
+    assert!(failure.gas_used() > 0);
+    let failure = failures.next().unwrap();
+    let Some(RevertReason::Panic { code, description }) = failure.revert_reason() else {
+        anyhow::bail!("A panic was expected, got {failure}");
+    };
+    assert_eq!(*code, U256::from(0x12));
+    assert_eq!(*description, "division or modulo by zero");
+    let failure = failures.next().unwrap();
+    let Some(RevertReason::Custom { name, arguments }) = failure.revert_reason() else {
+        anyhow::bail!("A custom error was expected, got {failure}");
+    };
+    assert_eq!(name, "InsufficientBalance");
+    assert_eq!(arguments.len(), 2);
+    assert_eq!(arguments[0].as_uint(), Some((U256::from(12), 256)));
+    assert_eq!(arguments[1].as_uint(), Some((U256::from(20), 256)));
+    let failure = failures.next().unwrap();
+    assert_eq!(failure.revert_reason(), Some(&RevertReason::Raw(Bytes::new())));
+    let failure = failures.next().unwrap();
+    assert!(matches!(failure, CallFailure::Halt { .. }), "A halt was expected, got {failure}");
+    Ok(())
+}
+
+/// Checks that the Transfer events of the ERC-20 token are decoded with
+/// their indexed parameters.
+fn event_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("event_scenario", ?spec_id);
+    let _guard = span.enter();
+    let (bytecode, abi) = erc20_bytecode_and_abi()?;
+
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let token_address = harness.deploy_with_abi(bytecode, abi)?;
+
+    let recipient = Address::with_last_byte(1);
+    let receipt = harness.call_receipt(token_address, transfer_args(recipient, U256::from(5)))?;
+    assert_eq!(receipt.logs.len(), 1);
+    let event = receipt.expect_event(
+        "Transfer",
+        &[
+            ("from", DynSolValue::Address(Address::ZERO)),
+            ("to", DynSolValue::Address(recipient)),
+            ("value", DynSolValue::Uint(U256::from(5), 256)),
+        ],
+    )?;
+    assert_eq!(event.address, token_address);
+    info!(%event, "event decoded");
+    assert!(receipt.expect_event("Transfer", &[("to", DynSolValue::Address(Address::ZERO))]).is_err());
+
+    let output = harness.static_call(token_address, balance_of_args(Address::ZERO))?;
+    assert_eq!(U256::from_be_slice(&output), U256::from(TOKEN_SUPPLY - 5));
+
+    // A failed transfer emits nothing.
+    let amount = U256::from(TOKEN_SUPPLY);
+    let error = harness.call_receipt(token_address, transfer_args(recipient, amount)).expect_err("The transfer should fail");
+    let failure = error.downcast::<CallFailure>()?;
+    assert_eq!(failure.revert_reason(), Some(&RevertReason::Error("insufficient balance".to_string())));
+    Ok(())
+}
+
+/// Runs the deployment of a token and transfers as one block and checks the
+/// receipts, then a block exceeding its gas limit.
+fn block_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("block_scenario", ?spec_id);
+    let _guard = span.enter();
+    let bytecode = erc20_bytecode()?;
+    let token_address = Address::ZERO.create(0);
+    let recipient = Address::with_last_byte(1);
+    let call = |data: Bytes| TxEnv {
+        kind: TxKind::Call(token_address),
+        data,
+        gas_limit: 1_000_000,
+        ..TxEnv::default()
+    };
+    let transactions = vec![
+        TxEnv {
+            kind: TxKind::Create,
+            data: bytecode,
+            gas_limit: 1_000_000,
+            ..TxEnv::default()
+        },
+        call(transfer_args(recipient, U256::from(5))),
+        call(transfer_args(recipient, U256::from(TOKEN_SUPPLY))),
+        call(transfer_args(recipient, U256::from(7))),
+    ];
+    let block = BlockEnv {
+        number: 1,
+        timestamp: 1_700_000_000,
+        gas_limit: 30_000_000,
+        ..BlockEnv::default()
+    };
+    let run_block = || -> anyhow::Result<BlockOutcome> {
+        let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+        harness.execute_block(block.clone(), transactions.clone())
+    };
+    let outcome = run_block()?;
+    let receipts = &outcome.receipts;
+    assert_eq!(receipts.len(), 4);
+    assert_eq!(receipts[0].contract_address, Some(token_address));
+    let statuses = receipts.iter().map(|receipt| receipt.success).collect::<Vec<_>>();
+    assert_eq!(statuses, [true, true, false, true]);
+    let mut cumulative_gas_used = 0;
+    for receipt in receipts {
+        cumulative_gas_used += receipt.gas_used;
+        assert_eq!(receipt.cumulative_gas_used, cumulative_gas_used);
+    }
+    assert_eq!(outcome.gas_used, cumulative_gas_used);
+    assert_eq!(receipts[1].logs.len(), 1);
+    assert!(receipts[2].logs.is_empty());
+    assert!(outcome.logs_bloom.contains_input(BloomInput::Raw(token_address.as_slice())));
+    assert!(!receipts[2].logs_bloom.contains_input(BloomInput::Raw(token_address.as_slice())));
+    assert_eq!(run_block()?.receipts_root, outcome.receipts_root);
+    info!(receipts_root = %outcome.receipts_root, gas_used = outcome.gas_used, "block executed");
+
+    // The second transfer does not fit in the gas left by the first one.
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    harness.deploy(erc20_bytecode()?)?;
+    let block = BlockEnv {
+        number: 2,
+        gas_limit: 100_000,
+        ..BlockEnv::default()
+    };
+    let transfer = |amount: u64| TxEnv {
+        gas_limit: 60_000,
+        ..call(transfer_args(recipient, U256::from(amount)))
+    };
+    let result = harness.execute_block(block, vec![transfer(5), transfer(7)]);
+    assert!(result.is_err(), "The block gas limit should be enforced");
+    // The rejected block leaves no state behind, not even its first transfer.
+    let output = harness.static_call(token_address, balance_of_args(recipient))?;
+    assert_eq!(U256::from_be_slice(&output), U256::ZERO);
+    let block = BlockEnv {
+        number: 2,
+        gas_limit: 200_000,
+        ..BlockEnv::default()
+    };
+    harness.execute_block(block, vec![transfer(5), transfer(7)])?;
+    let output = harness.static_call(token_address, balance_of_args(recipient))?;
+    assert_eq!(U256::from_be_slice(&output), U256::from(12));
+
+    // The block is committed as one, so a storage quota exceeded by its
+    // second transfer rolls back the first one too.
+    harness.db_mut().set_policy(StoragePolicy {
+        max_slots_per_contract: Some(2),
+        ..StoragePolicy::default()
+    });
+    let other_recipient = Address::with_last_byte(2);
+    let block = BlockEnv {
+        number: 3,
+        gas_limit: 200_000,
+        ..BlockEnv::default()
+    };
+    let transactions = vec![
+        transfer(1),
+        TxEnv {
+            gas_limit: 60_000,
+            ..call(transfer_args(other_recipient, U256::from(1)))
+        },
+    ];
+    let error = harness.execute_block(block, transactions).expect_err("The quota should be exceeded");
+    assert!(error.downcast_ref::<QuotaError>().is_some());
+    let output = harness.static_call(token_address, balance_of_args(recipient))?;
+    assert_eq!(U256::from_be_slice(&output), U256::from(12));
+    Ok(())
+}
+
+/// Estimates the gas of a call whose nested call needs more than the gas it
+/// uses because of the 63/64 rule, and checks that the estimate is the
+/// smallest gas limit that succeeds.
+fn estimate_gas_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("estimate_gas_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+contract Worker {
+  function work(uint256 rounds) external pure returns (uint256) {
+    uint256 x;
+    for (uint256 i = 0; i < rounds; i++) {
+      x += i * i;
+    }
+    return x;
+  }
+}
+
+contract Outer {
+  Worker worker;
+  uint256 public total;
+
+  constructor() {
+    worker = new Worker();
+  }
+
+  function run(uint256 rounds) external returns (uint256) {
+    total += rounds;
+    (bool success, bytes memory output) = address(worker).call(abi.encodeCall(Worker.work, (rounds)));
+    require(success, "the worker ran out of gas");
+    return abi.decode(output, (uint256));
+  }
+}
+"#
+    .to_string();
+    let (bytecode, abi) = get_bytecode_and_abi(&source_code, "Outer")?;
+
+    sol! {
+        function run(uint256 rounds) returns (uint256);
+        function total() returns (uint256);
+    }
+
+    let outer_address = Address::ZERO.create(0);
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    // The worker is created by the constructor of Outer.
+    harness.db_mut().register_contract_address(outer_address.create(1));
+    harness.deploy_with_abi(bytecode, abi)?;
+
+    let call = runCall { rounds: U256::from(2000) };
+    let nonce = harness.nonce(Address::ZERO)?;
+    let gas_used = harness.static_call_receipt(outer_address, call.abi_encode().into())?.gas_used;
+    let estimate = harness
+        .tx()
+        .estimate_gas(TxKind::Call(outer_address), call.abi_encode().into())?;
+    info!(gas_used, estimate, "gas estimated");
+    assert!(estimate > gas_used, "The 63/64 rule requires more gas than used");
+    let result = harness.tx().gas_limit(estimate - 1).static_call_typed(outer_address, &call);
+    assert!(result.is_err(), "The estimate should be minimal");
+    let expected = harness.tx().gas_limit(estimate).static_call_typed(outer_address, &call)?;
+
+    // Nothing has been committed by the read-only calls and the estimation.
+    assert_eq!(harness.static_call_typed(outer_address, &totalCall)?, U256::ZERO);
+    assert_eq!(harness.nonce(Address::ZERO)?, nonce);
+    assert_eq!(harness.tx().gas_limit(estimate).call_typed(outer_address, &call)?, expected);
+    assert_eq!(harness.static_call_typed(outer_address, &totalCall)?, U256::from(2000));
+
+    // A call failing whatever the gas returns its failure.
+    let error = harness
+        .tx()
+        .gas_limit(100_000)
+        .estimate_gas(TxKind::Call(outer_address), runCall { rounds: U256::from(100_000) }.abi_encode().into())
+        .expect_err("The worker cannot finish");
+    let failure = error.downcast::<CallFailure>()?;
+    assert_eq!(failure.revert_reason(), Some(&RevertReason::Error("the worker ran out of gas".to_string())));
+    Ok(())
+}
+
+/// Generates the access lists of a call reading the storage of another
+/// contract, which saves gas, and of an ERC-20 transfer, which only touches
+/// the warm recipient and does not.
+fn access_list_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("access_list_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+contract Registry {
+  uint256 a = 1;
+  uint256 b = 2;
+  uint256 c = 3;
+
+  function sum() external view returns (uint256) {
+    return a + b + c;
+  }
+}
+
+contract Reader {
+  Registry immutable registry;
+
+  constructor() {
+    registry = new Registry();
+  }
+
+  function read() external view returns (uint256) {
+    return registry.sum();
+  }
+}
+"#
+    .to_string();
+    let bytecode = get_bytecode(&source_code, "Reader")?;
+
+    sol! {
+        function read() returns (uint256);
+    }
+
+    let reader_address = Address::ZERO.create(0);
+    let registry_address = reader_address.create(1);
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    // The registry is created by the constructor of Reader.
+    harness.db_mut().register_contract_address(registry_address);
+    harness.deploy(bytecode)?;
+
+    let report = harness
+        .tx()
+        .create_access_list(TxKind::Call(reader_address), readCall.abi_encode().into())?;
+    let slots = (0..3).map(|slot| B256::from(U256::from(slot))).collect::<Vec<_>>();
+    assert_eq!(report.access_list, vec![(registry_address, slots)]);
+    // 100 gas saved on the registry and on each of its three slots.
+    assert_eq!(report.gas_used_without - report.gas_used_with, 400);
+    assert!(report.saves_gas());
+    let receipt = harness
+        .tx()
+        .access_list(report.access_list.clone())
+        .static_call_receipt(reader_address, readCall.abi_encode().into())?;
+    assert_eq!(receipt.gas_used, report.gas_used_with);
+    assert_eq!(readCall::abi_decode_returns(&receipt.output)?, U256::from(6));
+
+    // The balances of an ERC-20 transfer belong to the recipient of the
+    // transaction, which is warm from the start.
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let token_address = harness.deploy(erc20_bytecode()?)?;
+    let report = harness
+        .tx()
+        .create_access_list(TxKind::Call(token_address), transfer_args(Address::with_last_byte(1), U256::from(5)))?;
+    assert_eq!(report.access_list.len(), 1);
+    assert_eq!(report.access_list[0].1.len(), 2);
+    assert!(!report.saves_gas());
+    info!(?report, "access list of a transfer");
+    Ok(())
+}
+
+/// Runs transactions with different callers, values, gas limits, fee
+/// markets, access lists and chain ids.
+fn tx_env_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("tx_env_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+contract Vault {
+  address owner;
+  uint256 total;
+
+  constructor() {
+    owner = msg.sender;
+  }
+
+  function deposit() external payable returns (uint256) {
+    total += msg.value;
+    return total;
+  }
+
+  function owner_only() external view returns (address) {
+    require(msg.sender == owner, "not the owner");
+    return owner;
+  }
+
+  function spin(uint256 rounds) external returns (uint256) {
+    uint256 x;
+    for (uint256 i = 0; i < rounds; i++) {
+      x += i;
+      total = x;
+    }
+    return x;
+  }
+}
+"#
+    .to_string();
+    let bytecode = get_bytecode(&source_code, "Vault")?;
+
+    sol! {
+        function deposit() returns (uint256);
+        function owner_only() returns (address);
+        function spin(uint256 rounds) returns (uint256);
+    }
+
+    let alice = Address::repeat_byte(0xaa);
+    let initial_balance = U256::from(10).pow(U256::from(18));
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    add_user_accounts(harness.db_mut(), &[(alice, AccountInfo::from_balance(initial_balance))])?;
+    let vault_address = harness.deploy(bytecode)?;
+
+    // The msg.sender checks.
+    assert_eq!(harness.call_typed(vault_address, &owner_onlyCall)?, Address::ZERO);
+    let error = harness.tx().caller(alice).call_typed(vault_address, &owner_onlyCall).expect_err("alice is not the owner");
+    let failure = error.downcast::<CallFailure>()?;
+    assert_eq!(failure.revert_reason(), Some(&RevertReason::Error("not the owner".to_string())));
+
+    // A legacy transaction with value and gas price.
+    let balance = harness.db().basic_ref(alice)?.context("alice should exist")?.balance;
+    let value = U256::from(1000);
+    let receipt = harness
+        .tx()
+        .caller(alice)
+        .value(value)
+        .gas_price(2)
+        .call_receipt(vault_address, depositCall.abi_encode().into())?;
+    assert_eq!(depositCall::abi_decode_returns(&receipt.output)?, value);
+    let new_balance = harness.db().basic_ref(alice)?.context("alice should exist")?.balance;
+    assert_eq!(new_balance, balance - value - U256::from(receipt.gas_used * 2));
+
+    // An EIP-1559 transaction with an access list. With a zero base fee the
+    // price of the gas is the priority fee.
+    let balance = new_balance;
+    let receipt = harness
+        .tx()
+        .caller(alice)
+        .value(value)
+        .eip1559_fees(10, 1)
+        .access_list(vec![(vault_address, vec![B256::from(U256::from(1))])])
+        .call_receipt(vault_address, depositCall.abi_encode().into())?;
+    assert_eq!(depositCall::abi_decode_returns(&receipt.output)?, value * U256::from(2));
+    let new_balance = harness.db().basic_ref(alice)?.context("alice should exist")?.balance;
+    assert_eq!(new_balance, balance - value - U256::from(receipt.gas_used));
+
+    // Out of gas.
+    let error = harness
+        .tx()
+        .gas_limit(50_000)
+        .call_typed(vault_address, &spinCall { rounds: U256::from(1000) })
+        .expect_err("The gas limit is too low");
+    let failure = error.downcast::<CallFailure>()?;
+    assert!(matches!(failure, CallFailure::Halt { .. }), "An out of gas was expected, got {failure}");
+    assert_eq!(failure.gas_used(), 50_000);
+
+    // A transaction for another chain is rejected before execution.
+    let nonce = harness.nonce(Address::ZERO)?;
+    let error = harness
+        .tx()
+        .chain_id(Some(2))
+        .call_typed(vault_address, &owner_onlyCall)
+        .expect_err("The chain id should be checked");
+    assert!(matches!(
+        error.downcast_ref::<InvalidTransaction>(),
+        Some(InvalidTransaction::InvalidChainId)
+    ));
+    assert_eq!(harness.nonce(Address::ZERO)?, nonce);
+
+    // The nonce follows the committed transactions of each sender, a wrong
+    // one is rejected before execution.
+    let alice_nonce = harness.nonce(alice)?;
+    assert_eq!(harness.next_nonce(alice)?, alice_nonce);
+    let result = harness.tx().caller(alice).nonce(alice_nonce + 1).call_typed(vault_address, &owner_onlyCall);
+    assert!(result.is_err(), "The nonce of alice is too high");
+    harness.set_auto_nonce(false);
+    let result = harness.tx().call_typed(vault_address, &owner_onlyCall);
+    assert!(result.is_err(), "The default nonce 0 is already used");
+    harness.set_auto_nonce(true);
+    harness.tx().call_typed(vault_address, &owner_onlyCall)?;
+    assert_eq!(harness.nonce(Address::ZERO)?, nonce + 1);
+    Ok(())
+}
+
+/// Runs a vesting contract across blocks with `roll`, `warp` and
+/// `next_block`, then checks the coinbase, basefee, prevrandao and gas limit
+/// seen by the contract.
+fn block_env_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("block_env_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+contract Vesting {
+  uint256 constant DURATION = 1000;
+  uint256 constant TOTAL = 1000;
+
+  uint256 start;
+  uint256 unlock_block;
+  uint256 released;
+
+  constructor() {
+    start = block.timestamp;
+    unlock_block = block.number + 10;
+  }
+
+  function vested() public view returns (uint256) {
+    if (block.timestamp >= start + DURATION) {
+      return TOTAL;
+    }
+    return TOTAL * (block.timestamp - start) / DURATION;
+  }
+
+  function release() external returns (uint256) {
+    require(block.number >= unlock_block, "locked");
+    uint256 amount = vested() - released;
+    released += amount;
+    return amount;
+  }
+
+  function environment() external view returns (uint256 number, uint256 timestamp, address coinbase, uint256 basefee, uint256 prevrandao, uint256 gaslimit) {
+    return (block.number, block.timestamp, block.coinbase, block.basefee, block.prevrandao, block.gaslimit);
+  }
+}
+"#
+    .to_string();
+    let bytecode = get_bytecode(&source_code, "Vesting")?;
+
+    sol! {
+        function release() returns (uint256);
+        function environment() returns (uint256 number, uint256 timestamp, address coinbase, uint256 basefee, uint256 prevrandao, uint256 gaslimit);
+    }
+
+    let alice = Address::repeat_byte(0xaa);
+    let coinbase = Address::repeat_byte(0xcb);
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let initial_balance = U256::from(10).pow(U256::from(18));
+    add_user_accounts(
+        harness.db_mut(),
+        &[(alice, AccountInfo::from_balance(initial_balance)), (coinbase, AccountInfo::default())],
+    )?;
+    let start = 1_700_000_000;
+    harness.roll(100);
+    harness.warp(start);
+    let vesting_address = harness.deploy(bytecode)?;
+
+    // Nothing can be released before the unlock block.
+    let error = harness.call_typed(vesting_address, &releaseCall).expect_err("The vesting is locked");
+    let failure = error.downcast::<CallFailure>()?;
+    assert_eq!(failure.revert_reason(), Some(&RevertReason::Error("locked".to_string())));
+
+    for _ in 0..10 {
+        harness.next_block(12)?;
+    }
+    assert_eq!(harness.call_typed(vesting_address, &releaseCall)?, U256::from(120));
+    harness.skip(10_000)?;
+    assert_eq!(harness.call_typed(vesting_address, &releaseCall)?, U256::from(880));
+    assert_eq!(harness.call_typed(vesting_address, &releaseCall)?, U256::ZERO);
+
+    let prevrandao = B256::repeat_byte(0x42);
+    harness.set_coinbase(coinbase);
+    harness.set_basefee(7);
+    harness.set_prevrandao(prevrandao);
+    harness.set_block_gas_limit(40_000_000);
+    // The deployer has no balance, so it cannot pay the basefee.
+    let result = harness.static_call_typed(vesting_address, &environmentCall);
+    assert!(result.is_err(), "The gas price is below the basefee");
+
+    let environment = harness
+        .tx()
+        .caller(alice)
+        .gas_price(10)
+        .gas_limit(100_000)
+        .static_call_typed(vesting_address, &environmentCall)?;
+    assert_eq!(environment.number, U256::from(110));
+    assert_eq!(environment.timestamp, U256::from(start + 10 * 12 + 10_000));
+    assert_eq!(environment.coinbase, coinbase);
+    assert_eq!(environment.basefee, U256::from(7));
+    assert_eq!(B256::from(environment.prevrandao), prevrandao);
+    assert_eq!(environment.gaslimit, U256::from(40_000_000));
+
+    // The coinbase receives the priority fee of the committed transactions.
+    let receipt = harness
+        .tx()
+        .caller(alice)
+        .gas_price(10)
+        .gas_limit(100_000)
+        .call_receipt(vesting_address, releaseCall.abi_encode().into())?;
+    let coinbase_balance = harness.db().basic_ref(coinbase)?.context("The coinbase should exist")?.balance;
+    assert_eq!(coinbase_balance, U256::from(3 * receipt.gas_used));
+
+    // An overflowing timestamp is rejected and leaves the block unchanged.
+    harness.warp(u64::MAX);
+    assert!(harness.next_block(1).is_err());
+    assert_eq!(harness.block().number, 110);
+    assert_eq!(harness.block().timestamp, u64::MAX);
+    Ok(())
+}
+
+/// Runs the SELFDESTRUCT cases whose outcome depends on EIP-6780:
+/// destruction in the creating transaction, destruction in a later
+/// transaction and re-creation at the same address with CREATE2.
+fn selfdestruct_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("selfdestruct_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Child {
+  uint256 value;
+
+  constructor() payable {
+    value = 1;
+  }
+
+  function set_value(uint256 new_value) external {
+    value = new_value;
+  }
+
+  function get_value() external view returns (uint256) {
+    return value;
+  }
+
+  function destroy(address payable beneficiary) external {
+    selfdestruct(beneficiary);
+  }
+}
+
+contract Factory {
+  bytes32 constant SALT = bytes32(uint256(42));
+
+  function create_child(uint256 amount) external returns (address) {
+    Child child = new Child{salt: SALT, value: amount}();
+    return address(child);
+  }
+
+  function create_and_destroy_child(uint256 amount) external returns (address) {
+    Child child = new Child{salt: SALT, value: amount}();
+    child.destroy(payable(address(this)));
+    return address(child);
+  }
+}
+"#
+    .to_string();
+    let child_bytecode = get_bytecode(&source_code, "Child")?;
+    let factory_bytecode = get_bytecode(&source_code, "Factory")?;
+
+    sol! {
+        function create_child(uint256 amount) returns (address);
+        function create_and_destroy_child(uint256 amount) returns (address);
+        function set_value(uint256 new_value);
+        function get_value() returns (uint256);
+        function destroy(address beneficiary);
+    }
+
+    let is_cancun = spec_id.is_enabled_in(SpecId::CANCUN);
+    let initial_balance = U256::from(1000);
+    let amount = U256::from(10);
+    let factory_address = Address::ZERO.create(0);
+    let salt = B256::from(U256::from(42));
+    let child_address = factory_address.create2_from_code(salt, &child_bytecode);
+
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let db = harness.db_mut();
+    db.register_contract_address(child_address);
+    // Funding the address before the deployment keeps the creating
+    // transaction free of value transfers from the caller.
+    db.insert_account_info(factory_address, AccountInfo::from_balance(initial_balance))?;
+    let contract_address = harness.deploy(factory_bytecode)?;
+    assert_eq!(contract_address, factory_address);
+
+    // Case 1: created and destroyed in the same transaction. The contract
+    // disappears and its balance goes back to the factory for all specs.
+    let address = harness.call_typed(factory_address, &create_and_destroy_childCall { amount })?;
+    assert_eq!(address, child_address);
+    let db = harness.db();
+    assert_eq!(db.basic_ref(child_address)?, None);
+    assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::ZERO);
+    let factory_info = db.basic_ref(factory_address)?.context("factory should exist")?;
+    assert_eq!(factory_info.balance, initial_balance);
+
+    // Case 2: created in one transaction and destroyed in a later one.
+    let address = harness.call_typed(factory_address, &create_childCall { amount })?;
+    assert_eq!(address, child_address);
+    harness.call_typed(child_address, &set_valueCall { new_value: U256::from(5) })?;
+    harness.call_typed(child_address, &destroyCall { beneficiary: factory_address })?;
+
+    let db = harness.db();
+    let factory_info = db.basic_ref(factory_address)?.context("factory should exist")?;
+    assert_eq!(factory_info.balance, initial_balance);
+    let child_info = db.basic_ref(child_address)?;
+    if is_cancun {
+        // Only the balance is moved, code and storage are kept.
+        let child_info = child_info.context("child should survive SELFDESTRUCT")?;
+        assert_eq!(child_info.balance, U256::ZERO);
+        assert!(!child_info.is_empty_code_hash());
+        assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::from(5));
+        assert_eq!(harness.static_call_typed(child_address, &get_valueCall)?, U256::from(5));
+    } else {
+        assert_eq!(child_info, None);
+        assert_eq!(db.storage_ref(child_address, U256::ZERO)?, U256::ZERO);
+        // No stale slot should remain after the delete_key_prefix.
+        let dump = db.dump_account(child_address)?;
+        assert!(dump.info.is_none());
+        assert!(dump.storage.is_empty());
+    }
+    harness.db().print_status();
+    harness.db().reset_storage_stats();
+
+    // Case 3: re-creation at the same address. This is only possible if the
+    // previous contract was really destroyed.
+    let result = harness.call_typed(factory_address, &create_childCall { amount: U256::ZERO });
+    if is_cancun {
+        assert!(result.is_err(), "CREATE2 should collide with the surviving contract");
+        assert_eq!(harness.db().storage_ref(child_address, U256::ZERO)?, U256::from(5));
+    } else {
+        assert_eq!(result?, child_address);
+        // The storage of the re-created contract starts from scratch.
+        assert_eq!(harness.db().storage_ref(child_address, U256::ZERO)?, U256::from(1));
+        assert_eq!(harness.static_call_typed(child_address, &get_valueCall)?, U256::from(1));
+    }
+    harness.db().print_status();
+    debug!("state dump:\n{}", harness.db().dump_all()?);
+    Ok(())
+}
+
+
+/// Runs the key value map operations and ERC-20 transfers on an
+/// `InMemoryDB` and a `LineraDatabase` side by side, then checks that an
+/// account funded differently is reported as the divergence.
+fn differential_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("differential_scenario", ?spec_id);
+    let _guard = span.enter();
+    let db = ContractHarness::new_linera(spec_id, metrics)?.into_db();
+    let mut runner = DifferentialRunner::new(InMemoryDB::default(), db, spec_id);
+    let create = |data: Bytes| TxEnv {
+        kind: TxKind::Create,
+        data,
+        gas_limit: 1_000_000,
+        ..TxEnv::default()
+    };
+    let call = |contract_address: Address, data: Bytes| TxEnv {
+        kind: TxKind::Call(contract_address),
+        data,
+        gas_limit: 1_000_000,
+        ..TxEnv::default()
+    };
+
+    let map_address = Address::ZERO.create(0);
+    runner.execute(create(key_value_map_bytecode()?))?;
+    for operation in [Operation::InsertKeyValue(U256::from(7), U256::from(5)),
+                      Operation::InsertKeyValueBis(U256::from(8), U256::from(5)),
+                      Operation::ReadValue(U256::from(7)),
+                      Operation::DeleteKey(U256::from(7)),
+                      Operation::ReadValue(U256::from(7))] {
+        runner.execute(call(map_address, operation.abi_encode()))?;
+    }
+
+    let token_address = Address::ZERO.create(1);
+    runner.add_account(token_address, None)?;
+    runner.execute(create(erc20_bytecode()?))?;
+    let recipient = Address::with_last_byte(1);
+    let result = runner.execute(call(token_address, transfer_args(recipient, U256::from(5))))?;
+    assert!(result.is_success());
+    let result = runner.execute(call(token_address, transfer_args(recipient, U256::from(TOKEN_SUPPLY))))?;
+    assert!(!result.is_success());
+
+    // Only the balances differ, so the results agree and the divergence is
+    // found in the account infos after the commit.
+    let alice = Address::with_last_byte(0xa1);
+    let funded = |balance: u64| AccountInfo {
+        balance: U256::from(balance),
+        ..AccountInfo::default()
+    };
+    runner.left.db_mut().add_account(alice, Some(funded(10)))?;
+    runner.right.db_mut().add_account(alice, Some(funded(9)))?;
+    let tx = TxEnv {
+        caller: alice,
+        ..call(map_address, Operation::ReadValue(U256::from(8)).abi_encode())
+    };
+    let error = runner.execute(tx).expect_err("The balances of alice differ");
+    let divergence = error.downcast_ref::<Divergence>().context("The error should be a divergence")?;
+    let summary = |balance: u64| AccountSummary {
+        balance: U256::from(balance),
+        nonce: 1,
+        code_hash: funded(0).code_hash,
+    };
+    assert_eq!(
+        *divergence,
+        Divergence::AccountInfo {
+            transaction: 9,
+            address: alice,
+            left: summary(10),
+            right: summary(9),
+        }
+    );
+    info!("{divergence}");
+    Ok(())
+}
+
+/// Exports the state of a `LineraDatabase` after an ERC-20 transfer as a
+/// genesis alloc, and imports it into an `InMemoryDB` and another
+/// `LineraDatabase`, which export the same alloc and serve the same calls.
+fn genesis_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("genesis_scenario", ?spec_id);
+    let _guard = span.enter();
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let token_address = harness.deploy(erc20_bytecode()?)?;
+    let recipient = Address::with_last_byte(1);
+    harness.call(token_address, transfer_args(recipient, U256::from(5)))?;
+    let alloc = export_genesis_alloc(harness.db())?;
+    assert_eq!(alloc.keys().copied().collect::<Vec<_>>(), [Address::ZERO, token_address]);
+    assert_eq!(alloc[&Address::ZERO].nonce, U256::from(2));
+    assert_eq!(alloc[&token_address].storage.len(), 2);
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("alloc.json");
+    save_genesis_alloc(&path, &alloc)?;
+    assert_eq!(load_genesis_alloc(&path)?, alloc);
+
+    let short_path = dir.path().join("short.json");
+    let short_alloc = format!(r#"{{"{recipient}": {{"balance": "0x1", "storage": {{"0x01": "0x02"}}}}}}"#);
+    std::fs::write(&short_path, short_alloc)?;
+    let short_alloc = load_genesis_alloc(&short_path)?;
+    assert_eq!(short_alloc[&recipient].storage[&U256::from(1)], U256::from(2));
+    save_genesis_alloc(&short_path, &short_alloc)?;
+    let contents = std::fs::read_to_string(&short_path)?;
+    assert!(contents.contains(&format!("\"{}\": \"{}\"", B256::with_last_byte(1), B256::with_last_byte(2))));
+
+    let mut db = InMemoryDB::default();
+    import_genesis_alloc(&mut db, &alloc)?;
+    assert_eq!(export_genesis_alloc(&db)?, alloc);
+    let mut imported = ContractHarness::new(db, spec_id);
+    let output = imported.static_call(token_address, balance_of_args(recipient))?;
+    assert_eq!(U256::from_be_slice(&output), U256::from(5));
+
+    let mut imported = ContractHarness::new_linera(spec_id, metrics)?;
+    import_genesis_alloc(imported.db_mut(), &alloc)?;
+    assert_eq!(export_genesis_alloc(imported.db())?, alloc);
+    let output = imported.static_call(token_address, balance_of_args(recipient))?;
+    assert_eq!(U256::from_be_slice(&output), U256::from(5));
+    Ok(())
+}
+
+/// Reads a fixed-size array and the slot after it with `read_storage_range`
+/// and compares the values with the ones of `storage_ref`.
+fn storage_range_scenario(spec_id: SpecId, metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("storage_range_scenario", ?spec_id);
+    let _guard = span.enter();
+    let source_code = r#"
+contract FixedArray {
+  uint256[4] values;
+  uint256 count;
+
+  function set(uint256 index, uint256 value) external {
+    values[index] = value;
+    count += 1;
+  }
+}
+"#
+    .to_string();
+    let bytecode = get_bytecode(&source_code, "FixedArray")?;
+
+    sol! {
+        function set(uint256 index, uint256 value);
+    }
+
+    let mut harness = ContractHarness::new_linera(spec_id, metrics)?;
+    let contract_address = harness.deploy(bytecode)?;
+    for (index, value) in [(0, 5), (2, 7)] {
+        let data = setCall { index: U256::from(index), value: U256::from(value) }.abi_encode();
+        harness.call(contract_address, data.into())?;
+    }
+
+    let values = harness.db().read_storage_range(contract_address, U256::ZERO, 5)?;
+    assert_eq!(values, [5, 0, 7, 0, 2].map(U256::from));
+    for (index, value) in values.iter().enumerate() {
+        let Ok(expected) = harness.db().storage_ref(contract_address, U256::from(index)) else {
+            anyhow::bail!("The storage_ref failed for address={contract_address}");
+        };
+        assert_eq!(*value, expected);
+    }
+    let values = harness.db().read_storage_range(contract_address, U256::from(2), 3)?;
+    assert_eq!(values, [7, 0, 2].map(U256::from));
+    assert!(harness.db().read_storage_range(contract_address, U256::ZERO, 0)?.is_empty());
+    let values = harness.db().read_storage_range(contract_address, U256::MAX - U256::from(1), 5)?;
+    assert_eq!(values, vec![U256::ZERO; 2]);
+    Ok(())
+}
+
+/// Runs the GeneralStateTests fixture of `state_tests/` against both
+/// databases, which checks the rejection, the logs hash and the state root
+/// of every case.
+fn state_test_scenario(metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("state_test_scenario");
+    let _guard = span.enter();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("state_tests/sstore_and_log.json");
+    let tests = load_state_tests(&path)?;
+    let mut number_cases = 0;
+    for (name, test) in &tests {
+        for (fork, posts) in &test.post {
+            let spec_id = fork_spec_id(fork).with_context(|| format!("unknown fork {fork}"))?;
+            for post in posts {
+                run_state_test(test, spec_id, post, InMemoryDB::default())
+                    .with_context(|| format!("{name} {fork} on the InMemoryDB"))?;
+                let db = ContractHarness::new_linera(spec_id, metrics)?.into_db();
+                run_state_test(test, spec_id, post, db)
+                    .with_context(|| format!("{name} {fork} on the LineraDatabase"))?;
+                number_cases += 1;
+            }
+        }
+    }
+    assert_eq!(number_cases, 4);
+    info!(number_cases, "state tests passed");
+    Ok(())
+}
+
+/// Writes a store in the legacy key layout, with the tags 0 for
+/// `Address::ZERO` and 1 for `Address::ZERO.create(0)`, migrates it with
+/// `migrate_store` and reads it back through a `LineraDatabase`.
+fn migration_scenario(metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("migration_scenario");
+    let _guard = span.enter();
+    let contract_address = Address::ZERO.create(0);
+    let slot = U256::from(3);
+    let info = AccountInfo {
+        nonce: 1,
+        ..AccountInfo::default()
+    };
+    let legacy_storage_key = [vec![1, KeyCategory::Storage as u8], bcs::to_bytes(&slot)?].concat();
+    assert_eq!(legacy_storage_key.len(), 35);
+    let entries = vec![
+        (vec![0, KeyCategory::AccountInfo as u8], bcs::to_bytes(&info)?),
+        (vec![1, KeyCategory::AccountState as u8], bcs::to_bytes(&AccountState::Touched)?),
+        (legacy_storage_key, bcs::to_bytes(&U256::from(5))?),
+    ];
+    // Before the migration, the dump lists the legacy keys as undecoded.
+    let store = block_on(MemoryStore::new_test_store())?;
+    write_store_entries(&store, entries.clone())?;
+    let dump = LineraDatabase::with_metrics(store, metrics.clone()).dump_all()?;
+    assert!(dump.accounts.is_empty());
+    assert_eq!(dump.undecoded.len(), 3);
+    info!("legacy store dump:\n{dump}");
+
+    let store = block_on(MemoryStore::new_test_store())?;
+    write_store_entries(&store, entries)?;
+    assert_eq!(migrate_store(&store, &[])?, 3);
+    assert_eq!(migrate_store(&store, &[])?, 0);
+    let keys = read_store_entries(&store)?
+        .into_iter()
+        .map(|(key, _)| decode_key(&key))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        [
+            Some(DecodedKey::AccountInfo(Address::ZERO)),
+            Some(DecodedKey::AccountState(contract_address)),
+            Some(DecodedKey::Storage(contract_address, slot)),
+        ]
+    );
+
+    let db = LineraDatabase::with_metrics(store, metrics.clone());
+    let Ok(Some(migrated_info)) = db.basic_ref(Address::ZERO) else {
+        anyhow::bail!("The account info of Address::ZERO should have been migrated");
+    };
+    assert_eq!(migrated_info.nonce, 1);
+    let Ok(value) = db.storage_ref(contract_address, slot) else {
+        anyhow::bail!("The storage_ref failed for address={contract_address}");
+    };
+    assert_eq!(value, U256::from(5));
+    Ok(())
+}
+
+/// Replays a deployment, a SELFDESTRUCT and the calls around it under
+/// Shanghai, Cancun and Prague. Since EIP-6780 (Cancun) the storage is
+/// kept, so the last call returns the value instead of nothing.
+fn spec_diff_scenario(metrics: &Arc<StorageMetrics>) -> anyhow::Result<()> {
+    let span = info_span!("spec_diff_scenario");
+    let _guard = span.enter();
+    let source_code = r#"
+contract Ephemeral {
+  uint256 value = 5;
+
+  function get_value() external view returns (uint256) {
+    return value;
+  }
+
+  function destroy() external {
+    selfdestruct(payable(msg.sender));
+  }
+}
+"#
+    .to_string();
+    let bytecode = get_bytecode(&source_code, "Ephemeral")?;
+
+    sol! {
+        function get_value() returns (uint256);
+        function destroy();
+    }
+
+    let calls = [
+        get_valueCall.abi_encode(),
+        destroyCall.abi_encode(),
+        get_valueCall.abi_encode(),
+    ];
+    let scenario = Scenario {
+        name: "Ephemeral".to_string(),
+        deployer: Address::ZERO,
+        bytecode,
+        calls: calls.into_iter().map(|data| ScenarioCall::new(Address::ZERO, data.into())).collect(),
+    };
+    let spec_ids = [SpecId::SHANGHAI, SpecId::CANCUN, SpecId::PRAGUE];
+    let report = run_across_specs(&scenario, &spec_ids, || {
+        let db = block_on(MemoryStore::new_test_store())?;
+        Ok(LineraDatabase::with_metrics(db, metrics.clone()))
+    })?;
+    info!("{report}");
+    let differences = report.differences();
+    let last_step = scenario.calls.len();
+    assert!(differences.iter().any(|difference| {
+        difference.step == last_step && difference.spec_id == SpecId::CANCUN && difference.field == "output"
+    }));
+    for outcome in &report.outcomes {
+        let last_output = &outcome.steps[last_step].output;
+        let expected = if outcome.spec_id.is_enabled_in(SpecId::CANCUN) {
+            U256::from(5).to_be_bytes_vec()
+        } else {
+            Vec::new()
+        };
+        assert_eq!(last_output.to_vec(), expected);
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    init_tracing();
+    let metrics = Arc::new(StorageMetrics::new());
+    // With METRICS_ADDR=127.0.0.1:9898 the metrics stay available for
+    // scraping on /metrics after the scenarios are finished.
+    let server = match std::env::var("METRICS_ADDR") {
+        Ok(address) => Some(serve_metrics(metrics.clone(), address)?),
+        Err(_) => None,
+    };
+    for spec_id in [SpecId::SHANGHAI, SpecId::CANCUN] {
+        key_value_map_scenario(spec_id, &metrics)?;
+        prefetch_scenario(spec_id, &metrics)?;
+        quota_scenario(spec_id, &metrics)?;
+        speculative_scenario(spec_id, &metrics)?;
+        failure_scenario(spec_id, &metrics)?;
+        event_scenario(spec_id, &metrics)?;
+        block_scenario(spec_id, &metrics)?;
+        estimate_gas_scenario(spec_id, &metrics)?;
+        access_list_scenario(spec_id, &metrics)?;
+        tx_env_scenario(spec_id, &metrics)?;
+        block_env_scenario(spec_id, &metrics)?;
+        selfdestruct_scenario(spec_id, &metrics)?;
+        differential_scenario(spec_id, &metrics)?;
+        genesis_scenario(spec_id, &metrics)?;
+        storage_range_scenario(spec_id, &metrics)?;
+    }
+    spec_diff_scenario(&metrics)?;
+    migration_scenario(&metrics)?;
+    state_test_scenario(&metrics)?;
+
+    info!("The single_execution has been successful");
+    if let Some(server) = server {
+        server.join().expect("The metrics server should not panic");
+    } else {
+        debug!("metrics:\n{}", metrics.render());
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, ToSocketAddrs},
+    sync::Arc,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use revm_primitives::Address;
+use tracing::{info, warn};
+
+/// The registry of the metrics of the `LineraDatabase`.
+///
+/// It follows the counters of `StorageStats` but is never reset, so that
+/// the values can be scraped and graphed during long-running simulations.
+/// A single instance can be shared by several databases.
+pub struct StorageMetrics {
+    registry: Registry,
+    operations: IntCounterVec,
+    bytes_written: IntCounterVec,
+    fees: IntCounterVec,
+    batch_size: Histogram,
+    store_latency: HistogramVec,
+}
+
+impl Default for StorageMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StorageMetrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let operations = IntCounterVec::new(
+            Opts::new(
+                "linera_database_storage_operations_total",
+                "Number of storage operations (reset, set, release, warm_read, cold_read, prefetch)",
+            ),
+            &["address", "operation"],
+        )
+        .expect("The metric should be valid");
+        let bytes_written = IntCounterVec::new(
+            Opts::new(
+                "linera_database_bytes_written_total",
+                "Number of bytes of keys and values written to the store",
+            ),
+            &["address"],
+        )
+        .expect("The metric should be valid");
+        let fees = IntCounterVec::new(
+            Opts::new(
+                "linera_database_storage_fees_total",
+                "Storage fees charged according to the storage policy",
+            ),
+            &["address"],
+        )
+        .expect("The metric should be valid");
+        let batch_size = Histogram::with_opts(
+            HistogramOpts::new(
+                "linera_database_batch_size",
+                "Number of write operations in a committed batch",
+            )
+            .buckets(vec![1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0]),
+        )
+        .expect("The metric should be valid");
+        let store_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "linera_database_store_latency_seconds",
+                "Latency of the calls to the key-value store",
+            )
+            .buckets(prometheus::exponential_buckets(1e-6, 4.0, 12).expect("valid buckets")),
+            &["operation"],
+        )
+        .expect("The metric should be valid");
+        registry
+            .register(Box::new(operations.clone()))
+            .expect("The registration should be possible");
+        registry
+            .register(Box::new(bytes_written.clone()))
+            .expect("The registration should be possible");
+        registry
+            .register(Box::new(fees.clone()))
+            .expect("The registration should be possible");
+        registry
+            .register(Box::new(batch_size.clone()))
+            .expect("The registration should be possible");
+        registry
+            .register(Box::new(store_latency.clone()))
+            .expect("The registration should be possible");
+        Self {
+            registry,
+            operations,
+            bytes_written,
+            fees,
+            batch_size,
+            store_latency,
+        }
+    }
+
+    pub fn record_operation(&self, address: &Address, operation: &str) {
+        self.operations
+            .with_label_values(&[&address.to_string(), operation])
+            .inc();
+    }
+
+    pub fn record_bytes_written(&self, address: &Address, bytes: usize) {
+        self.bytes_written
+            .with_label_values(&[&address.to_string()])
+            .inc_by(bytes as u64);
+    }
+
+    pub fn record_fee(&self, address: &Address, fee: u64) {
+        self.fees.with_label_values(&[&address.to_string()]).inc_by(fee);
+    }
+
+    pub fn record_batch_size(&self, size: usize) {
+        self.batch_size.observe(size as f64);
+    }
+
+    pub fn record_store_latency(&self, operation: &str, latency: Duration) {
+        self.store_latency
+            .with_label_values(&[operation])
+            .observe(latency.as_secs_f64());
+    }
+
+    /// The number of calls to the key-value store so far, for all the
+    /// databases sharing these metrics.
+    pub fn store_calls(&self) -> u64 {
+        [
+            "read_value_bytes",
+            "read_multi_values_bytes",
+            "find_key_values_by_prefix",
+            "write_batch",
+        ]
+        .iter()
+        .map(|operation| self.store_latency.with_label_values(&[operation]).get_sample_count())
+        .sum()
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .expect("The text encoding should be possible")
+    }
+}
+
+/// Serves the metrics on `http://<address>/metrics` from a background thread.
+pub fn serve_metrics<A: ToSocketAddrs>(
+    metrics: Arc<StorageMetrics>,
+    address: A,
+) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(address)?;
+    info!(address = %listener.local_addr()?, "serving metrics");
+    Ok(std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    warn!(%error, "failed to accept a metrics connection");
+                    continue;
+                }
+            };
+            let mut request_line = String::new();
+            if let Err(error) = BufReader::new(&stream).read_line(&mut request_line) {
+                warn!(%error, "failed to read a metrics request");
+                continue;
+            }
+            let response = if request_line.starts_with("GET /metrics") {
+                let body = metrics.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+            };
+            if let Err(error) = stream.write_all(response.as_bytes()) {
+                warn!(%error, "failed to write a metrics response");
+            }
+        }
+    }))
+}
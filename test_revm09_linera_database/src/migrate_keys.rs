@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use anyhow::Context;
+use futures::executor::block_on;
+use linera_views::{memory::MemoryStore, store::TestKeyValueStore};
+use revm_primitives::Address;
+use test_linera_database::{
+    key_format::{
+        migrate_store, read_raw_snapshot, read_store_entries, write_raw_snapshot, write_store_entries,
+        KEY_FORMAT_VERSION,
+    },
+    logging::init_tracing,
+};
+use tracing::info;
+
+/// Converts a raw snapshot of a store from the legacy key layout to the
+/// layout of `KEY_FORMAT_VERSION`. The snapshot is loaded into a
+/// `MemoryStore`, which is migrated by `migrate_store` as a live store
+/// would be, and then written back.
+///
+/// The legacy layout only stored a key tag per account, so the addresses
+/// registered with `register_contract_address` have to be given in the
+/// order of registration.
+fn main() -> anyhow::Result<()> {
+    init_tracing();
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 3 {
+        anyhow::bail!(
+            "Usage: {} <input.json> <output.json> [registered_address ...]",
+            args[0]
+        );
+    }
+    let registered_addresses = args[3..]
+        .iter()
+        .map(|address| {
+            address
+                .parse::<Address>()
+                .with_context(|| format!("failed to parse address={address}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let entries = read_raw_snapshot(Path::new(&args[1]))?;
+    let number_entries = entries.len();
+    let store = block_on(MemoryStore::new_test_store())?;
+    write_store_entries(&store, entries)?;
+    let number_converted = migrate_store(&store, &registered_addresses)?;
+    let entries = read_store_entries(&store)?;
+    write_raw_snapshot(Path::new(&args[2]), &entries)?;
+    info!(
+        number_entries,
+        number_converted,
+        version = KEY_FORMAT_VERSION,
+        "The migration has been successful"
+    );
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use revm_primitives::Address;
+use serde::{Deserialize, Serialize};
+
+/// The storage policy applied by the `LineraDatabase` when committing a
+/// transaction. The default policy has no quota and charges no fee.
+#[derive(Clone, Debug, Default)]
+pub struct StoragePolicy {
+    /// The maximal number of non-zero slots of a contract.
+    pub max_slots_per_contract: Option<u64>,
+    /// The maximal number of bytes of the storage entries of a contract.
+    pub max_bytes_per_contract: Option<u64>,
+    /// The fee of a slot going from zero to non-zero.
+    pub fee_per_set: u64,
+    /// The fee of a slot going from non-zero to another non-zero value.
+    pub fee_per_reset: u64,
+    /// The fee of a slot going from non-zero to zero.
+    pub fee_per_release: u64,
+}
+
+impl StoragePolicy {
+    pub fn fee(&self, number_set: u64, number_reset: u64, number_release: u64) -> u64 {
+        number_set
+            .saturating_mul(self.fee_per_set)
+            .saturating_add(number_reset.saturating_mul(self.fee_per_reset))
+            .saturating_add(number_release.saturating_mul(self.fee_per_release))
+    }
+
+    pub fn check(&self, address: Address, usage: &StorageUsage) -> Result<(), QuotaError> {
+        if let Some(max_slots) = self.max_slots_per_contract {
+            if usage.slots > max_slots {
+                return Err(QuotaError::TooManySlots {
+                    address,
+                    slots: usage.slots,
+                    max_slots,
+                });
+            }
+        }
+        if let Some(max_bytes) = self.max_bytes_per_contract {
+            if usage.bytes > max_bytes {
+                return Err(QuotaError::TooManyBytes {
+                    address,
+                    bytes: usage.bytes,
+                    max_bytes,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The storage used by a contract. It is kept in the store next to the
+/// account info so that the quotas can be checked without a prefix scan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// The number of non-zero slots.
+    pub slots: u64,
+    /// The number of bytes of the keys and values of the storage entries.
+    pub bytes: u64,
+}
+
+/// The reason for rejecting the commit of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuotaError {
+    TooManySlots {
+        address: Address,
+        slots: u64,
+        max_slots: u64,
+    },
+    TooManyBytes {
+        address: Address,
+        bytes: u64,
+        max_bytes: u64,
+    },
+}
+
+impl fmt::Display for QuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaError::TooManySlots {
+                address,
+                slots,
+                max_slots,
+            } => write!(
+                f,
+                "contract {address} would use {slots} slots, the quota is {max_slots}"
+            ),
+            QuotaError::TooManyBytes {
+                address,
+                bytes,
+                max_bytes,
+            } => write!(
+                f,
+                "contract {address} would use {bytes} bytes of storage, the quota is {max_bytes}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QuotaError {}
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use futures::executor::block_on;
+use linera_views::{memory::MemoryStore, store::TestKeyValueStore};
+use revm_database::InMemoryDB;
+use test_linera_database::{
+    linera_database::LineraDatabase,
+    logging::init_tracing,
+    scenario_file::{load_scenario_file, run_scenario_file},
+};
+use tracing::{error, info};
+
+/// Runs scenario files (YAML, or JSON with the `json` extension) against
+/// the `LineraDatabase` on a `MemoryStore` or against the `InMemoryDB` of
+/// revm.
+fn main() -> anyhow::Result<()> {
+    init_tracing();
+    let args = std::env::args().collect::<Vec<_>>();
+    let (backend, files) = match &args[1..] {
+        [flag, backend, files @ ..] if flag == "--db" => (backend.as_str(), files),
+        files => ("linera", files),
+    };
+    if files.is_empty() || !["linera", "memory"].contains(&backend) {
+        anyhow::bail!("Usage: {} [--db linera|memory] <scenario> ...", args[0]);
+    }
+    let mut number_failed = 0;
+    for file in files {
+        let path = Path::new(file);
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        let scenario = load_scenario_file(path)?;
+        let result = match backend {
+            "memory" => run_scenario_file(&scenario, base_dir, InMemoryDB::default()).map(|_| ()),
+            _ => {
+                let db = block_on(MemoryStore::new_test_store())?;
+                run_scenario_file(&scenario, base_dir, LineraDatabase::new(db)).map(|_| ())
+            }
+        };
+        match result {
+            Ok(()) => info!(%file, backend, "PASS"),
+            Err(error) => {
+                error!(%file, backend, "FAIL: {error:#}");
+                number_failed += 1;
+            }
+        }
+    }
+    if number_failed > 0 {
+        anyhow::bail!("{number_failed} of {} scenarios failed", files.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::BTreeMap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use futures::executor::block_on;
+use linera_views::{memory::MemoryStore, store::TestKeyValueStore};
+use revm_database::InMemoryDB;
+use revm_primitives::hardfork::SpecId;
+use test_linera_database::{
+    linera_database::LineraDatabase,
+    logging::init_tracing,
+    state_test::{fork_spec_id, load_state_tests, run_state_test, PostState, StateTest},
+};
+use tracing::{info, warn};
+
+const BACKENDS: [&str; 2] = ["memory", "linera"];
+
+#[derive(Default)]
+struct Tally {
+    passed: usize,
+    failed: usize,
+}
+
+/// The JSON files of `path`, recursively if it is a directory.
+fn fixture_files(path: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            fixture_files(&entry, files)?;
+        }
+    } else if path.extension().is_some_and(|extension| extension == "json") {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn run_case(backend: &str, test: &StateTest, spec_id: SpecId, post: &PostState) -> anyhow::Result<()> {
+    // The `LineraDatabase` panics on the addresses it does not handle.
+    let result = catch_unwind(AssertUnwindSafe(|| match backend {
+        "memory" => run_state_test(test, spec_id, post, InMemoryDB::default()),
+        _ => {
+            let db = block_on(MemoryStore::new_test_store())?;
+            run_state_test(test, spec_id, post, LineraDatabase::new(db))
+        }
+    }));
+    match result {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            anyhow::bail!("panicked: {message}")
+        }
+    }
+}
+
+/// Runs the Ethereum GeneralStateTests fixtures of the given files or
+/// directories against the `InMemoryDB` of revm and the `LineraDatabase` on
+/// a `MemoryStore`, and reports the passed and failed cases per fork.
+fn main() -> anyhow::Result<()> {
+    init_tracing();
+    let args = std::env::args().collect::<Vec<_>>();
+    let (fork_filter, paths) = match &args[1..] {
+        [flag, fork, paths @ ..] if flag == "--fork" => (Some(fork.as_str()), paths),
+        paths => (None, paths),
+    };
+    if paths.is_empty() {
+        anyhow::bail!("Usage: {} [--fork <fork>] <fixture file or directory> ...", args[0]);
+    }
+    let mut files = Vec::new();
+    for path in paths {
+        fixture_files(Path::new(path), &mut files)?;
+    }
+    let mut tallies = BTreeMap::<(&str, String), Tally>::new();
+    for file in &files {
+        let tests = load_state_tests(file)?;
+        for (name, test) in &tests {
+            for (fork, posts) in &test.post {
+                if fork_filter.is_some_and(|fork_filter| fork_filter != fork) {
+                    continue;
+                }
+                let Some(spec_id) = fork_spec_id(fork) else {
+                    warn!(%name, %fork, "unknown fork, skipped");
+                    continue;
+                };
+                for (index, post) in posts.iter().enumerate() {
+                    for backend in BACKENDS {
+                        let tally = tallies.entry((backend, fork.clone())).or_default();
+                        match run_case(backend, test, spec_id, post) {
+                            Ok(()) => tally.passed += 1,
+                            Err(error) => {
+                                warn!(file = %file.display(), %name, %fork, index, backend, "FAIL: {error:#}");
+                                tally.failed += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut number_failed = 0;
+    for ((backend, fork), tally) in &tallies {
+        info!(backend, %fork, passed = tally.passed, failed = tally.failed, "state tests");
+        number_failed += tally.failed;
+    }
+    if number_failed > 0 {
+        anyhow::bail!("{number_failed} state test cases failed");
+    }
+    Ok(())
+}
@@ -0,0 +1,446 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use alloy_dyn_abi::{DynSolValue, FunctionExt, JsonAbiExt, Specifier};
+use alloy_json_abi::{Function, JsonAbi, Param};
+use anyhow::Context;
+use revm::primitives::Bytes;
+use revm_primitives::{hardfork::SpecId, Address, U256};
+use revm_state::AccountInfo;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{info, info_span};
+
+use crate::{
+    backend::Backend,
+    call_failure::{CallFailure, RevertReason},
+    harness::{CallReceipt, ContractHarness},
+    solidity::get_bytecode_and_abi,
+};
+
+/// A scenario written in YAML or JSON: the Solidity sources, the accounts
+/// and the steps (deployments, calls, storage checks and block changes).
+///
+/// The arguments, return values and event parameters are written as in
+/// `cast`: numbers, addresses, strings or nested lists, parsed with the
+/// types of the ABI.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioFile {
+    pub name: String,
+    /// The hardfork, for example `CANCUN`. The latest one by default.
+    #[serde(default)]
+    pub spec_id: Option<SpecId>,
+    pub sources: Vec<Source>,
+    /// The users, which the `LineraDatabase` needs registered.
+    #[serde(default)]
+    pub accounts: Vec<AccountEntry>,
+    pub steps: Vec<Step>,
+}
+
+/// A Solidity source, either a file relative to the scenario file or
+/// inline code.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Source {
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountEntry {
+    pub address: Address,
+    #[serde(default)]
+    pub balance: Option<Value>,
+}
+
+/// The `deny_unknown_fields` of the enum applies to the fields of `Roll`
+/// and `Warp`, the other steps have their own.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    Deploy(DeployStep),
+    Call(CallStep),
+    Storage(StorageCheck),
+    Roll { number: u64 },
+    Warp { timestamp: u64 },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeployStep {
+    /// The name of the contract in the sources.
+    pub contract: String,
+    /// The name used by the later steps, the contract name by default.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub args: Vec<Value>,
+    #[serde(default)]
+    pub from: Option<Address>,
+    #[serde(default)]
+    pub value: Option<Value>,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CallStep {
+    /// The name of the deployment.
+    pub contract: String,
+    /// The function name, or its signature for overloaded functions.
+    pub function: String,
+    #[serde(default)]
+    pub args: Vec<Value>,
+    #[serde(default)]
+    pub from: Option<Address>,
+    #[serde(default)]
+    pub value: Option<Value>,
+    /// Whether the state changes are discarded.
+    #[serde(default, rename = "static")]
+    pub static_call: bool,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+/// The expected outcome of a step. Without `revert` or `fails`, the step
+/// has to succeed.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    #[serde(default)]
+    pub returns: Option<Vec<Value>>,
+    /// The revert string, panic description or custom error name.
+    #[serde(default)]
+    pub revert: Option<String>,
+    /// Whether any failure, halts included, is expected.
+    #[serde(default)]
+    pub fails: bool,
+    #[serde(default)]
+    pub events: Vec<ExpectedEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectedEvent {
+    pub name: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+}
+
+/// Checks the storage of a deployment. The counts of operations are the
+/// ones since the start of the scenario and are only checked against the
+/// databases that count them.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageCheck {
+    pub contract: String,
+    /// The number of non-zero slots.
+    #[serde(default)]
+    pub slots: Option<u64>,
+    /// The values of raw slots.
+    #[serde(default)]
+    pub values: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub set: Option<u64>,
+    #[serde(default)]
+    pub reset: Option<u64>,
+    #[serde(default)]
+    pub release: Option<u64>,
+}
+
+/// Reads a scenario file, in JSON if its extension is `json` and in YAML
+/// otherwise.
+pub fn load_scenario_file(path: &Path) -> anyhow::Result<ScenarioFile> {
+    let contents = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let scenario = if path.extension().is_some_and(|extension| extension == "json") {
+        serde_json::from_str(&contents)?
+    } else {
+        serde_yaml::from_str(&contents)?
+    };
+    Ok(scenario)
+}
+
+/// Runs `scenario` against `db`, the files of the sources being relative to
+/// `base_dir`. The first failed step is returned as an error.
+pub fn run_scenario_file<DB: Backend>(scenario: &ScenarioFile, base_dir: &Path, db: DB) -> anyhow::Result<DB> {
+    let span = info_span!("run_scenario_file", name = %scenario.name);
+    let _guard = span.enter();
+    let sources = scenario
+        .sources
+        .iter()
+        .map(|source| match (&source.file, &source.code) {
+            (Some(file), None) => std::fs::read_to_string(base_dir.join(file))
+                .with_context(|| format!("failed to read {}", file.display())),
+            (None, Some(code)) => Ok(code.clone()),
+            _ => anyhow::bail!("A source needs either a file or some code"),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut harness = ContractHarness::new(db, scenario.spec_id.unwrap_or_default());
+    for account in &scenario.accounts {
+        let balance = match &account.balance {
+            Some(balance) => parse_uint(balance)?,
+            None => U256::ZERO,
+        };
+        harness.db_mut().add_account(account.address, Some(AccountInfo::from_balance(balance)))?;
+    }
+    let mut deployments = BTreeMap::<String, Address>::new();
+    for (index, step) in scenario.steps.iter().enumerate() {
+        let span = info_span!("step", index);
+        let _guard = span.enter();
+        let result = match step {
+            Step::Deploy(deploy) => run_deploy(&mut harness, &sources, deploy).map(|address| {
+                let name = deploy.name.clone().unwrap_or_else(|| deploy.contract.clone());
+                if let Some(address) = address {
+                    deployments.insert(name, address);
+                }
+            }),
+            Step::Call(call) => run_call(&mut harness, &deployments, call),
+            Step::Storage(check) => run_storage_check(&harness, &deployments, check),
+            Step::Roll { number } => {
+                harness.roll(*number);
+                Ok(())
+            }
+            Step::Warp { timestamp } => {
+                harness.warp(*timestamp);
+                Ok(())
+            }
+        };
+        result.with_context(|| format!("step {index} of {} failed", scenario.name))?;
+    }
+    info!(steps = scenario.steps.len(), "scenario passed");
+    Ok(harness.into_db())
+}
+
+fn run_deploy<DB: Backend>(
+    harness: &mut ContractHarness<DB>,
+    sources: &[String],
+    deploy: &DeployStep,
+) -> anyhow::Result<Option<Address>> {
+    let (bytecode, abi) = compile(sources, &deploy.contract)?;
+    let mut data = bytecode.to_vec();
+    if let Some(constructor) = abi.constructor() {
+        let values = coerce_values(&constructor.inputs, &deploy.args)?;
+        data.extend(constructor.abi_encode_input(&values)?);
+    } else if !deploy.args.is_empty() {
+        anyhow::bail!("The contract {} has no constructor arguments", deploy.contract);
+    }
+    let from = deploy.from.unwrap_or(harness.deployer());
+    let contract_address = from.create(harness.next_nonce(from)?);
+    harness.db_mut().add_account(contract_address, None)?;
+    let mut tx = harness.tx().caller(from);
+    if let Some(value) = &deploy.value {
+        tx = tx.value(parse_uint(value)?);
+    }
+    let Some(contract_address) = check_failure(&deploy.expect, tx.deploy(data.into()))? else {
+        return Ok(None);
+    };
+    harness.set_abi(contract_address, abi);
+    Ok(Some(contract_address))
+}
+
+fn run_call<DB: Backend>(
+    harness: &mut ContractHarness<DB>,
+    deployments: &BTreeMap<String, Address>,
+    call: &CallStep,
+) -> anyhow::Result<()> {
+    let contract_address = *deployments
+        .get(&call.contract)
+        .with_context(|| format!("no deployment named {}", call.contract))?;
+    let abi = harness.abi(&contract_address).context("the deployments have an ABI")?;
+    let function = find_function(abi, &call.function)?.clone();
+    let values = coerce_values(&function.inputs, &call.args)?;
+    let data = Bytes::from(function.abi_encode_input(&values)?);
+    let mut tx = harness.tx();
+    if let Some(from) = call.from {
+        tx = tx.caller(from);
+    }
+    if let Some(value) = &call.value {
+        tx = tx.value(parse_uint(value)?);
+    }
+    let result = if call.static_call {
+        tx.static_call_receipt(contract_address, data)
+    } else {
+        tx.call_receipt(contract_address, data)
+    };
+    let Some(receipt) = check_failure(&call.expect, result)? else {
+        return Ok(());
+    };
+    check_receipt(&call.expect, &function, &receipt)
+}
+
+fn run_storage_check<DB: Backend>(
+    harness: &ContractHarness<DB>,
+    deployments: &BTreeMap<String, Address>,
+    check: &StorageCheck,
+) -> anyhow::Result<()> {
+    let contract_address = *deployments
+        .get(&check.contract)
+        .with_context(|| format!("no deployment named {}", check.contract))?;
+    let storage = harness.db().storage_slots(contract_address)?;
+    if let Some(slots) = check.slots {
+        anyhow::ensure!(
+            storage.len() as u64 == slots,
+            "{} has {} slots instead of {slots}",
+            check.contract,
+            storage.len()
+        );
+    }
+    for (slot, value) in &check.values {
+        let slot = parse_uint(&Value::String(slot.clone()))?;
+        let value = parse_uint(value)?;
+        let actual = storage.get(&slot).copied().unwrap_or_default();
+        anyhow::ensure!(actual == value, "The slot {slot} is {actual} instead of {value}");
+    }
+    if check.set.is_none() && check.reset.is_none() && check.release.is_none() {
+        return Ok(());
+    }
+    let Some(counts) = harness.db().storage_counts() else {
+        info!("The database does not count the storage operations, the counts are not checked");
+        return Ok(());
+    };
+    for (name, expected, actual) in [
+        ("set", check.set, counts.number_set),
+        ("reset", check.reset, counts.number_reset),
+        ("release", check.release, counts.number_release),
+    ] {
+        if let Some(expected) = expected {
+            anyhow::ensure!(actual == expected, "{actual} slots {name} instead of {expected}");
+        }
+    }
+    Ok(())
+}
+
+/// Returns the value of a successful step, `None` for an expected failure.
+fn check_failure<T>(expect: &Expectation, result: anyhow::Result<T>) -> anyhow::Result<Option<T>> {
+    let failure_expected = expect.fails || expect.revert.is_some();
+    let error = match result {
+        Ok(value) => {
+            anyhow::ensure!(!failure_expected, "The step succeeded while a failure was expected");
+            return Ok(Some(value));
+        }
+        Err(error) => error,
+    };
+    let Some(failure) = error.downcast_ref::<CallFailure>() else {
+        return Err(error);
+    };
+    if !failure_expected {
+        return Err(error);
+    }
+    if let Some(expected) = &expect.revert {
+        anyhow::ensure!(
+            revert_matches(failure.revert_reason(), expected),
+            "The failure is {failure} instead of {expected:?}"
+        );
+    }
+    Ok(None)
+}
+
+fn check_receipt(expect: &Expectation, function: &Function, receipt: &CallReceipt) -> anyhow::Result<()> {
+    if let Some(returns) = &expect.returns {
+        let expected = coerce_values(&function.outputs, returns)?;
+        let actual = function.abi_decode_output(&receipt.output)?;
+        anyhow::ensure!(actual == expected, "{} returned {actual:?} instead of {expected:?}", function.name);
+    }
+    for event in &expect.events {
+        let found = receipt.events_named(&event.name).any(|decoded| {
+            event.params.iter().all(|(name, value)| {
+                let Some(actual) = decoded.param(name) else {
+                    return false;
+                };
+                let expected = actual
+                    .as_type()
+                    .and_then(|ty| ty.coerce_str(&value_to_string(value)).ok());
+                expected.as_ref() == Some(actual)
+            })
+        });
+        if !found {
+            let emitted = receipt.events.iter().map(ToString::to_string).collect::<Vec<_>>();
+            anyhow::bail!("No event {} with {:?}, the emitted events are {emitted:?}", event.name, event.params);
+        }
+    }
+    Ok(())
+}
+
+fn revert_matches(reason: Option<&RevertReason>, expected: &str) -> bool {
+    match reason {
+        Some(RevertReason::Error(message)) => message == expected,
+        Some(RevertReason::Panic { description, .. }) => *description == expected,
+        Some(RevertReason::Custom { name, .. }) => name == expected,
+        Some(RevertReason::Raw(output)) => output.to_string() == expected,
+        None => false,
+    }
+}
+
+/// Compiles the first source defining `contract`. If none does, the errors
+/// of every source are reported, so that a compilation error is not taken
+/// for a missing contract.
+fn compile(sources: &[String], contract: &str) -> anyhow::Result<(Bytes, JsonAbi)> {
+    let mut errors = Vec::new();
+    for (index, source) in sources.iter().enumerate() {
+        match get_bytecode_and_abi(source, contract) {
+            Ok(compiled) => return Ok(compiled),
+            Err(error) => errors.push(format!("source {index}: {error:#}")),
+        }
+    }
+    anyhow::bail!(
+        "The contract {contract} is not found in the sources:\n{}",
+        errors.join("\n")
+    )
+}
+
+fn find_function<'a>(abi: &'a JsonAbi, function: &str) -> anyhow::Result<&'a Function> {
+    if function.contains('(') {
+        return abi
+            .functions()
+            .find(|candidate| candidate.signature() == function)
+            .with_context(|| format!("no function {function}"));
+    }
+    match abi.function(function).map(Vec::as_slice) {
+        Some([function]) => Ok(function),
+        Some(_) => anyhow::bail!("The function {function} is overloaded, its signature is needed"),
+        None => anyhow::bail!("no function {function}"),
+    }
+}
+
+fn coerce_values(params: &[Param], values: &[Value]) -> anyhow::Result<Vec<DynSolValue>> {
+    anyhow::ensure!(
+        params.len() == values.len(),
+        "{} values given for {} parameters",
+        values.len(),
+        params.len()
+    );
+    params
+        .iter()
+        .zip(values)
+        .map(|(param, value)| {
+            let ty = param.resolve()?;
+            ty.coerce_str(&value_to_string(value))
+                .with_context(|| format!("failed to parse {value} as {ty}"))
+        })
+        .collect()
+}
+
+fn parse_uint(value: &Value) -> anyhow::Result<U256> {
+    let value = value_to_string(value);
+    value
+        .parse::<U256>()
+        .with_context(|| format!("failed to parse {value} as an integer"))
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Array(values) => {
+            let values = values.iter().map(value_to_string).collect::<Vec<_>>();
+            format!("[{}]", values.join(","))
+        }
+        value => value.to_string(),
+    }
+}
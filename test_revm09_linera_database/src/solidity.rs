@@ -0,0 +1,120 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use alloy_json_abi::JsonAbi;
+use anyhow::Context;
+use revm_primitives::Bytes;
+use tempfile::tempdir;
+
+pub fn write_compilation_json(path: &Path, file_name: &str) {
+    let mut source = File::create(path).unwrap();
+    writeln!(
+        source,
+        r#"
+{{
+  "language": "Solidity",
+  "sources": {{
+    "{file_name}": {{
+      "urls": ["./{file_name}"]
+    }}
+  }},
+  "settings": {{
+    "viaIR": true,
+    "evmVersion": "shanghai",
+    "outputSelection": {{
+      "*": {{
+        "*": ["abi", "evm.bytecode"]
+      }}
+    }}
+  }}
+}}
+"#
+    )
+    .unwrap();
+}
+
+/// Compiles the file and returns the JSON output of the contract.
+fn get_contract_json_path(
+    path: &Path,
+    file_name: &str,
+    contract_name: &str,
+) -> anyhow::Result<serde_json::Value> {
+    let config_path = path.join("config.json");
+    write_compilation_json(&config_path, file_name);
+    let config_file = File::open(config_path)?;
+
+    let output_path = path.join("result.json");
+    let output_file = File::create(output_path.clone())?;
+
+    let status = Command::new("solc")
+        .current_dir(path)
+        .arg("--standard-json")
+        .stdin(Stdio::from(config_file))
+        .stdout(Stdio::from(output_file))
+        .status()?;
+    assert!(status.success());
+
+    let contents = std::fs::read_to_string(output_path)?;
+    let json_data: serde_json::Value = serde_json::from_str(&contents)?;
+    let contracts = json_data
+        .get("contracts")
+        .with_context(|| format!("failed to get contracts in json_data={}", json_data))?;
+    let file_name_contract = contracts
+        .get(file_name)
+        .context("failed to get {file_name}")?;
+    let test_data = file_name_contract
+        .get(contract_name)
+        .context("failed to get contract_name={contract_name}")?;
+    Ok(test_data.clone())
+}
+
+fn bytecode_from_json(test_data: &serde_json::Value) -> anyhow::Result<Bytes> {
+    let evm_data = test_data.get("evm").context("failed to get evm")?;
+    let bytecode = evm_data.get("bytecode").context("failed to get bytecode")?;
+    let object = bytecode.get("object").context("failed to get object")?;
+    let object = object.to_string();
+    let object = object.trim_matches(|c| c == '"').to_string();
+    let object = hex::decode(&object)?;
+    Ok(Bytes::copy_from_slice(&object))
+}
+
+fn abi_from_json(test_data: &serde_json::Value) -> anyhow::Result<JsonAbi> {
+    let abi = test_data.get("abi").context("failed to get abi")?;
+    Ok(serde_json::from_value(abi.clone())?)
+}
+
+pub fn get_bytecode_path(
+    path: &Path,
+    file_name: &str,
+    contract_name: &str,
+) -> anyhow::Result<Bytes> {
+    let test_data = get_contract_json_path(path, file_name, contract_name)?;
+    bytecode_from_json(&test_data)
+}
+
+pub fn get_bytecode(source_code: &str, contract_name: &str) -> anyhow::Result<Bytes> {
+    let dir = tempdir().unwrap();
+    let path = dir.path();
+    let file_name = "test_code.sol";
+    let test_code_path = path.join(file_name);
+    let mut test_code_file = File::create(&test_code_path)?;
+    writeln!(test_code_file, "{}", source_code)?;
+    get_bytecode_path(path, file_name, contract_name)
+}
+
+/// Returns the bytecode and the ABI of the contract, the latter being
+/// needed to decode the custom errors and the events.
+pub fn get_bytecode_and_abi(source_code: &str, contract_name: &str) -> anyhow::Result<(Bytes, JsonAbi)> {
+    let dir = tempdir().unwrap();
+    let path = dir.path();
+    let file_name = "test_code.sol";
+    let test_code_path = path.join(file_name);
+    let mut test_code_file = File::create(&test_code_path)?;
+    writeln!(test_code_file, "{}", source_code)?;
+    let test_data = get_contract_json_path(path, file_name, contract_name)?;
+    Ok((bytecode_from_json(&test_data)?, abi_from_json(&test_data)?))
+}
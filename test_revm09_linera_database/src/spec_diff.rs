@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, fmt};
+
+use revm::primitives::Bytes;
+use revm_context::result::{ExecutionResult, Output};
+use revm_primitives::{hardfork::SpecId, Address, Log, TxKind, U256};
+use revm_state::EvmState;
+use tracing::{info, info_span};
+
+use crate::{backend::Backend, harness::ContractHarness};
+
+/// A contract deployment followed by calls to the deployed contract.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub name: String,
+    pub deployer: Address,
+    pub bytecode: Bytes,
+    pub calls: Vec<ScenarioCall>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScenarioCall {
+    pub caller: Address,
+    pub value: U256,
+    pub data: Bytes,
+}
+
+impl ScenarioCall {
+    /// A call from `caller` without value.
+    pub fn new(caller: Address, data: Bytes) -> Self {
+        Self {
+            caller,
+            value: U256::ZERO,
+            data,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepStatus {
+    Success,
+    Revert,
+    Halt(String),
+    /// The transaction did not pass the validation of revm, or its commit
+    /// was rejected by the database.
+    Rejected(String),
+}
+
+/// The outcome of the deployment (step 0) or of a call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepOutcome {
+    pub status: StepStatus,
+    pub output: Bytes,
+    pub gas_used: u64,
+    /// The slots changed by the step with their new values.
+    pub storage: BTreeMap<(Address, U256), U256>,
+    pub logs: Vec<Log>,
+}
+
+impl StepOutcome {
+    fn rejected(error: anyhow::Error) -> Self {
+        Self {
+            status: StepStatus::Rejected(error.to_string()),
+            output: Bytes::new(),
+            gas_used: 0,
+            storage: BTreeMap::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    fn new(result: ExecutionResult, state: &EvmState) -> Self {
+        let storage = state
+            .iter()
+            .flat_map(|(address, account)| {
+                account
+                    .changed_storage_slots()
+                    .map(move |(slot, value)| ((*address, *slot), value.present_value()))
+            })
+            .collect();
+        let (status, output, gas_used, logs) = match result {
+            ExecutionResult::Success { output, gas_used, logs, .. } => {
+                let output = match output {
+                    Output::Call(output) => output,
+                    Output::Create(output, _) => output,
+                };
+                (StepStatus::Success, output, gas_used, logs)
+            }
+            ExecutionResult::Revert { output, gas_used } => (StepStatus::Revert, output, gas_used, Vec::new()),
+            ExecutionResult::Halt { reason, gas_used } => {
+                (StepStatus::Halt(format!("{reason:?}")), Bytes::new(), gas_used, Vec::new())
+            }
+        };
+        Self {
+            status,
+            output,
+            gas_used,
+            storage,
+            logs,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SpecOutcome {
+    pub spec_id: SpecId,
+    pub steps: Vec<StepOutcome>,
+}
+
+/// A field of a step whose value under `spec_id` differs from the one under
+/// the baseline spec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub step: usize,
+    pub spec_id: SpecId,
+    pub field: &'static str,
+    pub baseline: String,
+    pub value: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {}: {} under {:?} is {} instead of {}",
+            self.step, self.field, self.spec_id, self.value, self.baseline
+        )
+    }
+}
+
+/// The outcomes of a scenario under several specs, the first one being the
+/// baseline of the comparison.
+#[derive(Clone, Debug)]
+pub struct SpecReport {
+    pub scenario: String,
+    pub outcomes: Vec<SpecOutcome>,
+}
+
+impl SpecReport {
+    pub fn differences(&self) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        let Some((baseline, others)) = self.outcomes.split_first() else {
+            return differences;
+        };
+        for other in others {
+            for (step, (expected, actual)) in baseline.steps.iter().zip(&other.steps).enumerate() {
+                let fields: [(&'static str, String, String); 5] = [
+                    ("status", format!("{:?}", expected.status), format!("{:?}", actual.status)),
+                    ("output", expected.output.to_string(), actual.output.to_string()),
+                    ("gas used", expected.gas_used.to_string(), actual.gas_used.to_string()),
+                    ("storage", format!("{:?}", expected.storage), format!("{:?}", actual.storage)),
+                    ("logs", format!("{:?}", expected.logs), format!("{:?}", actual.logs)),
+                ];
+                for (field, baseline, value) in fields {
+                    if baseline != value {
+                        differences.push(Difference {
+                            step,
+                            spec_id: other.spec_id,
+                            field,
+                            baseline,
+                            value,
+                        });
+                    }
+                }
+            }
+        }
+        differences
+    }
+}
+
+impl fmt::Display for SpecReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spec_ids = self.outcomes.iter().map(|outcome| outcome.spec_id).collect::<Vec<_>>();
+        writeln!(f, "scenario {} under {spec_ids:?}", self.scenario)?;
+        let differences = self.differences();
+        if differences.is_empty() {
+            return writeln!(f, "  no difference");
+        }
+        for difference in differences {
+            writeln!(f, "  {difference}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Replays `scenario` under every spec of `spec_ids` against a fresh
+/// database from `make_db` and records the outcome of every step. All the
+/// steps are committed, including the failed ones.
+pub fn run_across_specs<DB, F>(scenario: &Scenario, spec_ids: &[SpecId], mut make_db: F) -> anyhow::Result<SpecReport>
+where
+    DB: Backend,
+    F: FnMut() -> anyhow::Result<DB>,
+{
+    let mut outcomes = Vec::new();
+    for spec_id in spec_ids {
+        let span = info_span!("run_across_specs", scenario = %scenario.name, ?spec_id);
+        let _guard = span.enter();
+        let mut harness = ContractHarness::with_deployer(make_db()?, *spec_id, scenario.deployer);
+        let contract_address = scenario.deployer.create(harness.nonce(scenario.deployer)?);
+        let mut steps = Vec::new();
+        let transactions = std::iter::once((scenario.deployer, TxKind::Create, U256::ZERO, scenario.bytecode.clone()))
+            .chain(
+                scenario
+                    .calls
+                    .iter()
+                    .map(|call| (call.caller, TxKind::Call(contract_address), call.value, call.data.clone())),
+            );
+        for (caller, kind, value, data) in transactions {
+            let result = harness.tx().caller(caller).value(value).transact(kind, data);
+            let step = match result {
+                Ok(result_and_state) => {
+                    let step = StepOutcome::new(result_and_state.result, &result_and_state.state);
+                    match harness.commit(result_and_state.state) {
+                        Ok(()) => step,
+                        Err(error) => StepOutcome::rejected(error),
+                    }
+                }
+                Err(error) => StepOutcome::rejected(error),
+            };
+            steps.push(step);
+        }
+        outcomes.push(SpecOutcome {
+            spec_id: *spec_id,
+            steps,
+        });
+    }
+    let report = SpecReport {
+        scenario: scenario.name.clone(),
+        outcomes,
+    };
+    info!(differences = report.differences().len(), "scenario replayed");
+    Ok(report)
+}
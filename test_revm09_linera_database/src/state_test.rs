@@ -0,0 +1,346 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use alloy_consensus::{
+    proofs::{state_root_unhashed, storage_root_unhashed},
+    TrieAccount,
+};
+use anyhow::Context;
+use revm::{
+    bytecode::Bytecode,
+    precompile::{PrecompileSpecId, Precompiles},
+};
+use revm_context::{
+    result::{InvalidHeader, InvalidTransaction},
+    transaction::{AccessList, AccessListItem},
+    BlockEnv, TxEnv,
+};
+use revm_primitives::{hardfork::SpecId, keccak256, Address, Bytes, TxKind, B256, U256};
+use revm_state::AccountInfo;
+use serde::Deserialize;
+use tracing::{debug, info_span};
+
+use crate::{backend::Backend, harness::ContractHarness};
+
+/// A test of the Ethereum GeneralStateTests: a pre-state, a transaction
+/// whose data, gas limit and value are picked by index, and the expected
+/// post-state of every fork.
+#[derive(Debug, Deserialize)]
+pub struct StateTest {
+    pub env: TestEnv,
+    pub pre: BTreeMap<Address, PreAccount>,
+    pub transaction: TestTransaction,
+    pub post: BTreeMap<String, Vec<PostState>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestEnv {
+    pub current_coinbase: Address,
+    pub current_gas_limit: U256,
+    pub current_number: U256,
+    pub current_timestamp: U256,
+    pub current_base_fee: Option<U256>,
+    pub current_difficulty: Option<U256>,
+    pub current_random: Option<B256>,
+    pub current_excess_blob_gas: Option<U256>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreAccount {
+    pub balance: U256,
+    pub code: Bytes,
+    pub nonce: U256,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestTransaction {
+    pub data: Vec<Bytes>,
+    pub gas_limit: Vec<U256>,
+    pub value: Vec<U256>,
+    pub gas_price: Option<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub nonce: U256,
+    /// Given by the recent fixtures. The older ones only have the secret
+    /// key, from which the sender is not recovered here.
+    pub sender: Option<Address>,
+    /// Empty for a contract creation.
+    #[serde(default)]
+    pub to: Option<String>,
+    /// The access list of each data, if the transaction is of type 1 or
+    /// more.
+    pub access_lists: Option<Vec<Option<Vec<AccessListEntry>>>>,
+    #[serde(default)]
+    pub blob_versioned_hashes: Vec<B256>,
+    pub max_fee_per_blob_gas: Option<U256>,
+    pub authorization_list: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListEntry {
+    pub address: Address,
+    pub storage_keys: Vec<B256>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostState {
+    /// The state root after the transaction.
+    pub hash: B256,
+    /// The keccak256 hash of the RLP encoding of the logs.
+    pub logs: B256,
+    pub indexes: PostIndexes,
+    /// Set if the transaction is invalid and has to be rejected.
+    pub expect_exception: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct PostIndexes {
+    pub data: usize,
+    pub gas: usize,
+    pub value: usize,
+}
+
+/// Loads the tests of a fixture file, keyed by name.
+pub fn load_state_tests(path: &Path) -> anyhow::Result<BTreeMap<String, StateTest>> {
+    let contents = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// The `SpecId` of a fork name of the fixtures. The transition forks, like
+/// `BerlinToLondonAt5`, are only used by the blockchain tests.
+pub fn fork_spec_id(fork: &str) -> Option<SpecId> {
+    let spec_id = match fork {
+        "Frontier" => SpecId::FRONTIER,
+        "Homestead" => SpecId::HOMESTEAD,
+        "EIP150" => SpecId::TANGERINE,
+        "EIP158" => SpecId::SPURIOUS_DRAGON,
+        "Byzantium" => SpecId::BYZANTIUM,
+        "Constantinople" => SpecId::CONSTANTINOPLE,
+        "ConstantinopleFix" => SpecId::PETERSBURG,
+        "Istanbul" => SpecId::ISTANBUL,
+        "Berlin" => SpecId::BERLIN,
+        "London" => SpecId::LONDON,
+        "Paris" | "Merge" => SpecId::MERGE,
+        "Shanghai" => SpecId::SHANGHAI,
+        "Cancun" => SpecId::CANCUN,
+        "Prague" => SpecId::PRAGUE,
+        "Osaka" => SpecId::OSAKA,
+        _ => return None,
+    };
+    Some(spec_id)
+}
+
+fn to_u64(value: U256, field: &str) -> anyhow::Result<u64> {
+    u64::try_from(value).ok().with_context(|| format!("{field} {value} does not fit in a u64"))
+}
+
+fn to_u128(value: U256, field: &str) -> anyhow::Result<u128> {
+    u128::try_from(value).ok().with_context(|| format!("{field} {value} does not fit in a u128"))
+}
+
+impl StateTest {
+    pub fn block_env(&self, spec_id: SpecId) -> anyhow::Result<BlockEnv> {
+        let env = &self.env;
+        let mut block = BlockEnv {
+            number: to_u64(env.current_number, "currentNumber")?,
+            beneficiary: env.current_coinbase,
+            timestamp: to_u64(env.current_timestamp, "currentTimestamp")?,
+            gas_limit: to_u64(env.current_gas_limit, "currentGasLimit")?,
+            basefee: env
+                .current_base_fee
+                .map(|basefee| to_u64(basefee, "currentBaseFee"))
+                .transpose()?
+                .unwrap_or_default(),
+            difficulty: env.current_difficulty.unwrap_or_default(),
+            ..BlockEnv::default()
+        };
+        if let Some(random) = env.current_random {
+            block.prevrandao = Some(random);
+        }
+        if let Some(excess_blob_gas) = env.current_excess_blob_gas {
+            let excess_blob_gas = to_u64(excess_blob_gas, "currentExcessBlobGas")?;
+            block.set_blob_excess_gas_and_price(excess_blob_gas, spec_id.is_enabled_in(SpecId::PRAGUE));
+        }
+        Ok(block)
+    }
+
+    /// The transaction selected by `indexes`.
+    pub fn tx_env(&self, indexes: PostIndexes) -> anyhow::Result<TxEnv> {
+        let transaction = &self.transaction;
+        let caller = transaction
+            .sender
+            .context("The sender is missing, it is not recovered from the secret key")?;
+        if transaction
+            .authorization_list
+            .as_ref()
+            .is_some_and(|list| list.as_array().is_none_or(|list| !list.is_empty()))
+        {
+            anyhow::bail!("The EIP-7702 authorization lists are not supported");
+        }
+        let data = transaction.data.get(indexes.data).context("The data index is out of range")?;
+        let gas_limit = transaction.gas_limit.get(indexes.gas).context("The gas index is out of range")?;
+        let value = transaction.value.get(indexes.value).context("The value index is out of range")?;
+        let kind = match transaction.to.as_deref() {
+            None | Some("") => TxKind::Create,
+            Some(to) => TxKind::Call(to.parse().with_context(|| format!("invalid recipient {to}"))?),
+        };
+        let gas_price = transaction
+            .gas_price
+            .or(transaction.max_fee_per_gas)
+            .context("The gas price is missing")?;
+        let access_list = transaction
+            .access_lists
+            .as_ref()
+            .and_then(|access_lists| access_lists.get(indexes.data).cloned().flatten())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| AccessListItem {
+                address: entry.address,
+                storage_keys: entry.storage_keys,
+            })
+            .collect::<Vec<_>>();
+        let tx_type = if !transaction.blob_versioned_hashes.is_empty() {
+            3
+        } else if transaction.max_priority_fee_per_gas.is_some() {
+            2
+        } else if transaction.access_lists.is_some() {
+            1
+        } else {
+            0
+        };
+        Ok(TxEnv {
+            tx_type,
+            caller,
+            gas_limit: to_u64(*gas_limit, "gasLimit")?,
+            gas_price: to_u128(gas_price, "gasPrice")?,
+            kind,
+            value: *value,
+            data: data.clone(),
+            nonce: to_u64(transaction.nonce, "nonce")?,
+            chain_id: Some(1),
+            access_list: AccessList::from(access_list),
+            gas_priority_fee: transaction
+                .max_priority_fee_per_gas
+                .map(|fee| to_u128(fee, "maxPriorityFeePerGas"))
+                .transpose()?,
+            blob_hashes: transaction.blob_versioned_hashes.clone(),
+            max_fee_per_blob_gas: transaction
+                .max_fee_per_blob_gas
+                .map(|fee| to_u128(fee, "maxFeePerBlobGas"))
+                .transpose()?
+                .unwrap_or_default(),
+            ..TxEnv::default()
+        })
+    }
+
+    /// The addresses that the transaction may create or touch without them
+    /// being in the pre-state, and that the `LineraDatabase` has to know
+    /// beforehand: the coinbase, the recipient, the precompiles and the
+    /// first contract created by the sender and by each pre-state contract.
+    /// The other ones, like the CREATE2 addresses or an empty account
+    /// receiving a value, make the `LineraDatabase` panic.
+    fn expected_addresses(&self, tx: &TxEnv, spec_id: SpecId) -> BTreeSet<Address> {
+        let mut addresses = Precompiles::new(PrecompileSpecId::from_spec_id(spec_id))
+            .addresses()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        addresses.insert(self.env.current_coinbase);
+        addresses.insert(match tx.kind {
+            TxKind::Call(address) => address,
+            TxKind::Create => tx.caller.create(tx.nonce),
+        });
+        for (address, account) in &self.pre {
+            if !account.code.is_empty() {
+                let nonce = u64::try_from(account.nonce).unwrap_or_default();
+                addresses.insert(address.create(nonce));
+            }
+        }
+        addresses
+    }
+}
+
+/// Runs the transaction of `post` on `db` under `spec_id` and checks the
+/// rejection, the logs hash and the state root. The first mismatch is
+/// returned as an error. An expected exception is only matched by a
+/// rejection of the transaction or of the block, not by an error of `db`.
+pub fn run_state_test<DB: Backend>(test: &StateTest, spec_id: SpecId, post: &PostState, db: DB) -> anyhow::Result<()> {
+    let span = info_span!("run_state_test", ?spec_id, data = post.indexes.data, gas = post.indexes.gas, value = post.indexes.value);
+    let _guard = span.enter();
+    let tx = test.tx_env(post.indexes)?;
+    let mut harness = ContractHarness::new(db, spec_id);
+    harness.set_block(test.block_env(spec_id)?);
+    for address in test.expected_addresses(&tx, spec_id) {
+        harness.db_mut().add_account(address, None)?;
+    }
+    let mut addresses = BTreeSet::from([test.env.current_coinbase]);
+    for (address, account) in &test.pre {
+        let bytecode = Bytecode::new_raw(account.code.clone());
+        let info = AccountInfo::new(account.balance, to_u64(account.nonce, "nonce")?, bytecode.hash_slow(), bytecode);
+        harness.db_mut().insert_account(*address, info, &account.storage)?;
+        addresses.insert(*address);
+    }
+
+    let logs = match (harness.transact_tx(tx), &post.expect_exception) {
+        (Ok(result_and_state), None) => {
+            addresses.extend(result_and_state.state.keys().copied());
+            harness.commit(result_and_state.state)?;
+            result_and_state.result.into_logs()
+        }
+        (Ok(result_and_state), Some(exception)) => {
+            anyhow::bail!("The transaction should be rejected with {exception}, got {:?}", result_and_state.result)
+        }
+        (Err(error), None) => return Err(error.context("The transaction should be valid")),
+        (Err(error), Some(exception)) if is_rejection(&error) => {
+            debug!(%exception, "transaction rejected: {error:#}");
+            Vec::new()
+        }
+        (Err(error), Some(exception)) => {
+            return Err(error.context(format!("The transaction should be rejected with {exception}")))
+        }
+    };
+    let logs_hash = keccak256(alloy_rlp::encode(&logs));
+    anyhow::ensure!(logs_hash == post.logs, "The logs hash is {logs_hash} instead of {}", post.logs);
+    let state_root = state_root(harness.db(), &addresses, spec_id)?;
+    anyhow::ensure!(state_root == post.hash, "The state root is {state_root} instead of {}", post.hash);
+    Ok(())
+}
+
+/// Whether `error` is a rejection of the transaction or of the block, as
+/// opposed to an error of the database.
+fn is_rejection(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<InvalidTransaction>().is_some() || error.downcast_ref::<InvalidHeader>().is_some()
+}
+
+/// The state root over `addresses`. Since EIP-161 (Spurious Dragon) the
+/// empty accounts are not part of the state.
+pub fn state_root<DB: Backend>(db: &DB, addresses: &BTreeSet<Address>, spec_id: SpecId) -> anyhow::Result<B256> {
+    let mut accounts = Vec::new();
+    for address in addresses {
+        let Ok(info) = db.basic_ref(*address) else {
+            anyhow::bail!("The basic_ref failed for address={address}");
+        };
+        let Some(info) = info else {
+            continue;
+        };
+        if info.is_empty() && spec_id.is_enabled_in(SpecId::SPURIOUS_DRAGON) {
+            continue;
+        }
+        let storage = db.storage_slots(*address)?;
+        let storage_root = storage_root_unhashed(storage.into_iter().map(|(slot, value)| (B256::from(slot), value)));
+        let account = TrieAccount {
+            nonce: info.nonce,
+            balance: info.balance,
+            storage_root,
+            code_hash: info.code_hash,
+        };
+        accounts.push((*address, account));
+    }
+    Ok(state_root_unhashed(accounts))
+}
@@ -0,0 +1,194 @@
+use alloy_sol_types::SolCall;
+use revm::primitives::Bytes;
+use revm_context::{
+    result::ResultAndState,
+    transaction::{AccessList, AccessListItem},
+    TxEnv,
+};
+use revm_primitives::{Address, TxKind, B256, U256};
+
+use crate::{
+    access_list::AccessListReport,
+    backend::Backend,
+    harness::{CallReceipt, ContractHarness},
+};
+
+const LEGACY_TX_TYPE: u8 = 0;
+const EIP2930_TX_TYPE: u8 = 1;
+const EIP1559_TX_TYPE: u8 = 2;
+
+/// A transaction of a `ContractHarness`, obtained from `ContractHarness::tx`.
+///
+/// The fields not set keep the values of `TxEnv::default()`, except for the
+/// nonce which is managed by the harness unless given. The type of
+/// the transaction follows from the fields: EIP-1559 if a priority fee is
+/// set, EIP-2930 if an access list is set, legacy otherwise.
+pub struct TxBuilder<'a, DB> {
+    harness: &'a mut ContractHarness<DB>,
+    tx: TxEnv,
+    nonce: Option<u64>,
+}
+
+impl<'a, DB> TxBuilder<'a, DB>
+where
+    DB: Backend,
+{
+    pub(crate) fn new(harness: &'a mut ContractHarness<DB>, caller: Address) -> Self {
+        let tx = TxEnv {
+            caller,
+            ..TxEnv::default()
+        };
+        Self {
+            harness,
+            tx,
+            nonce: None,
+        }
+    }
+
+    pub fn caller(mut self, caller: Address) -> Self {
+        self.tx.caller = caller;
+        self
+    }
+
+    /// Sets the nonce, bypassing the nonce management of the harness, for
+    /// example to test the nonce errors.
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    pub fn value(mut self, value: U256) -> Self {
+        self.tx.value = value;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.tx.gas_limit = gas_limit;
+        self
+    }
+
+    /// Sets the gas price of a legacy or EIP-2930 transaction.
+    pub fn gas_price(mut self, gas_price: u128) -> Self {
+        self.tx.gas_price = gas_price;
+        self.tx.gas_priority_fee = None;
+        self
+    }
+
+    /// Sets the fees of an EIP-1559 transaction.
+    pub fn eip1559_fees(mut self, max_fee_per_gas: u128, max_priority_fee_per_gas: u128) -> Self {
+        self.tx.gas_price = max_fee_per_gas;
+        self.tx.gas_priority_fee = Some(max_priority_fee_per_gas);
+        self
+    }
+
+    /// Sets the EIP-2930 access list as pairs of address and storage keys.
+    pub fn access_list(mut self, access_list: Vec<(Address, Vec<B256>)>) -> Self {
+        let items = access_list
+            .into_iter()
+            .map(|(address, storage_keys)| AccessListItem {
+                address,
+                storage_keys,
+            })
+            .collect::<Vec<_>>();
+        self.tx.access_list = AccessList::from(items);
+        self
+    }
+
+    /// Sets the chain id of the transaction, `None` for a pre-EIP-155 one.
+    pub fn chain_id(mut self, chain_id: Option<u64>) -> Self {
+        self.tx.chain_id = chain_id;
+        self
+    }
+
+    /// Returns the environment of the transaction, with the nonce given
+    /// to `nonce` or the next one of the caller.
+    pub fn build(self) -> anyhow::Result<TxEnv> {
+        let Self { harness, tx, nonce } = self;
+        complete(harness, tx, nonce)
+    }
+
+    fn into_parts(self, kind: TxKind, data: Bytes) -> anyhow::Result<(&'a mut ContractHarness<DB>, TxEnv)> {
+        let Self { harness, mut tx, nonce } = self;
+        tx.kind = kind;
+        tx.data = data;
+        let tx = complete(harness, tx, nonce)?;
+        Ok((harness, tx))
+    }
+
+    /// Executes the transaction without committing it.
+    pub fn transact(self, kind: TxKind, data: Bytes) -> anyhow::Result<ResultAndState> {
+        let (harness, tx) = self.into_parts(kind, data)?;
+        harness.transact_tx(tx)
+    }
+
+    pub fn deploy(self, bytecode: Bytes) -> anyhow::Result<Address> {
+        let (harness, tx) = self.into_parts(TxKind::Create, bytecode)?;
+        harness.deploy_tx(tx)
+    }
+
+    pub fn call(self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<Bytes> {
+        Ok(self.call_receipt(contract_address, encoded_args)?.output)
+    }
+
+    pub fn call_receipt(self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<CallReceipt> {
+        let (harness, tx) = self.into_parts(TxKind::Call(contract_address), encoded_args)?;
+        harness.call_receipt_tx(tx, true)
+    }
+
+    pub fn static_call(self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<Bytes> {
+        Ok(self.static_call_receipt(contract_address, encoded_args)?.output)
+    }
+
+    pub fn static_call_receipt(self, contract_address: Address, encoded_args: Bytes) -> anyhow::Result<CallReceipt> {
+        let (harness, tx) = self.into_parts(TxKind::Call(contract_address), encoded_args)?;
+        harness.call_receipt_tx(tx, false)
+    }
+
+    /// Returns the smallest gas limit for which the transaction succeeds,
+    /// see `ContractHarness::estimate_gas`.
+    pub fn estimate_gas(self, kind: TxKind, data: Bytes) -> anyhow::Result<u64> {
+        let (harness, tx) = self.into_parts(kind, data)?;
+        harness.estimate_gas(tx)
+    }
+
+    /// Generates the access list of the transaction, see
+    /// `ContractHarness::create_access_list`.
+    pub fn create_access_list(self, kind: TxKind, data: Bytes) -> anyhow::Result<AccessListReport> {
+        let (harness, tx) = self.into_parts(kind, data)?;
+        harness.create_access_list(tx)
+    }
+
+    pub fn call_typed<C: SolCall>(self, contract_address: Address, call: &C) -> anyhow::Result<C::Return> {
+        let output = self.call(contract_address, call.abi_encode().into())?;
+        Ok(C::abi_decode_returns(&output)?)
+    }
+
+    pub fn static_call_typed<C: SolCall>(self, contract_address: Address, call: &C) -> anyhow::Result<C::Return> {
+        let output = self.static_call(contract_address, call.abi_encode().into())?;
+        Ok(C::abi_decode_returns(&output)?)
+    }
+}
+
+/// Sets the nonce and the type of the transaction.
+fn complete<DB>(harness: &mut ContractHarness<DB>, mut tx: TxEnv, nonce: Option<u64>) -> anyhow::Result<TxEnv>
+where
+    DB: Backend,
+{
+    tx.nonce = match nonce {
+        Some(nonce) => nonce,
+        None if harness.auto_nonce() => harness.next_nonce(tx.caller)?,
+        None => tx.nonce,
+    };
+    tx.tx_type = tx_type(&tx);
+    Ok(tx)
+}
+
+fn tx_type(tx: &TxEnv) -> u8 {
+    if tx.gas_priority_fee.is_some() {
+        EIP1559_TX_TYPE
+    } else if !tx.access_list.0.is_empty() {
+        EIP2930_TX_TYPE
+    } else {
+        LEGACY_TX_TYPE
+    }
+}
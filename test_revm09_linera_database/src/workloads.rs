@@ -0,0 +1,137 @@
+use alloy_json_abi::JsonAbi;
+use alloy_sol_types::{sol, SolCall};
+use revm::primitives::Bytes;
+use revm_primitives::{Address, U256};
+
+use crate::solidity::{get_bytecode, get_bytecode_and_abi};
+
+/// The operations of the `ExampleKeyValueMap` contract.
+#[derive(Clone, Copy, Debug)]
+pub enum Operation {
+    DeleteKey(U256),
+    InsertKeyValue(U256,U256),
+    InsertKeyValueBis(U256,U256),
+    ReadValue(U256),
+}
+
+impl Operation {
+    pub fn key(&self) -> U256 {
+        match self {
+            Operation::DeleteKey(key)
+            | Operation::InsertKeyValue(key, _)
+            | Operation::InsertKeyValueBis(key, _)
+            | Operation::ReadValue(key) => *key,
+        }
+    }
+
+    pub fn abi_encode(&self) -> Bytes {
+        sol! {
+            function insert_key_value(uint256 key, uint256 value);
+            function insert_key_value_bis(uint256 key, uint256 value);
+            function delete_key(uint256 key);
+            function read_value(uint256 key);
+        }
+
+        match *self {
+            Operation::DeleteKey(key) => {
+                let fct_args = delete_keyCall { key };
+                fct_args.abi_encode().into()
+            },
+            Operation::InsertKeyValue(key, value) => {
+                let fct_args = insert_key_valueCall { key, value };
+                fct_args.abi_encode().into()
+            },
+            Operation::InsertKeyValueBis(key, value) => {
+                let fct_args = insert_key_value_bisCall { key, value };
+                fct_args.abi_encode().into()
+            },
+            Operation::ReadValue(key) => {
+                let fct_args = read_valueCall { key };
+                fct_args.abi_encode().into()
+            },
+        }
+    }
+}
+
+pub fn key_value_map_bytecode() -> anyhow::Result<Bytes> {
+    let source_code = r#"
+contract ExampleKeyValueMap {
+  mapping(uint256 => uint256) map;
+
+
+  function insert_key_value(uint256 key, uint256 value) external returns (uint256) {
+    map[key] = value;
+  }
+
+  function insert_key_value_bis(uint256 key, uint256 value) external returns (uint256) {
+    map[key] = value;
+    map[key] = value + 1;
+  }
+
+  function delete_key(uint256 key) external returns (uint256) {
+    delete map[key];
+  }
+
+  function read_value(uint256 key) external returns (uint256) {
+    return map[key];
+  }
+
+}
+"#
+    .to_string();
+
+    get_bytecode(&source_code, "ExampleKeyValueMap")
+}
+
+/// The total supply of `ExampleToken`, minted to the deployer.
+pub const TOKEN_SUPPLY: u64 = 1_000_000_000;
+
+/// A minimal ERC-20 style token, for the workloads touching many slots.
+fn erc20_source_code() -> String {
+    r#"
+contract ExampleToken {
+  mapping(address => uint256) balances;
+
+  event Transfer(address indexed from, address indexed to, uint256 value);
+
+  constructor() {
+    balances[msg.sender] = 1000000000;
+  }
+
+  function transfer(address to, uint256 amount) external returns (bool) {
+    require(balances[msg.sender] >= amount, "insufficient balance");
+    balances[msg.sender] -= amount;
+    balances[to] += amount;
+    emit Transfer(msg.sender, to, amount);
+    return true;
+  }
+
+  function balanceOf(address owner) external view returns (uint256) {
+    return balances[owner];
+  }
+}
+"#
+    .to_string()
+}
+
+pub fn erc20_bytecode() -> anyhow::Result<Bytes> {
+    get_bytecode(&erc20_source_code(), "ExampleToken")
+}
+
+pub fn erc20_bytecode_and_abi() -> anyhow::Result<(Bytes, JsonAbi)> {
+    get_bytecode_and_abi(&erc20_source_code(), "ExampleToken")
+}
+
+pub fn transfer_args(to: Address, amount: U256) -> Bytes {
+    sol! {
+        function transfer(address to, uint256 amount) returns (bool);
+    }
+    transferCall { to, amount }.abi_encode().into()
+}
+
+pub fn balance_of_args(owner: Address) -> Bytes {
+    sol! {
+        function balanceOf(address owner) returns (uint256);
+    }
+    balanceOfCall { owner }.abi_encode().into()
+}